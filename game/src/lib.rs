@@ -2,48 +2,83 @@
 
 // NOTE(aalhendi): Services that the game provides to the platform layer
 
-use core::f32;
+use core::{f32, ffi};
 use interface::GameButton::{MoveDown, MoveLeft, MoveRight, MoveUp};
-use interface::{GameInput, GameMemory, GameOffscreenBuffer, GameSoundOutputBuffer, ThreadContext};
+use interface::{
+    GAME_API_HASH, GameInput, GameMemory, GameOffscreenBuffer, GameSoundOutputBuffer, MemoryArena,
+    ThreadContext,
+};
 
-#[derive(Default)]
 #[repr(C)]
 pub struct GameState {
     player_x: f32,
     player_y: f32,
+    /// The rest of permanent storage, after the GameState itself.
+    permanent_arena: MemoryArena,
+}
+
+const TILEMAP: [[u32; 16]; 9] = [
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
+    [0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
+    [0, 1, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
+    [0, 1, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
+    [0, 1, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
+];
+
+// NOTE(aalhendi): more bands than cores, so a slow band doesn't leave the other threads idle.
+const RENDER_BAND_COUNT: usize = 16;
+
+/// One horizontal band of the backbuffer for a worker thread to draw the frame into.
+struct RenderWork {
+    // NOTE(aalhendi): only this band's rows, row 0 being `band_top` in the full backbuffer.
+    buffer: GameOffscreenBuffer,
+    band_top: i32,
+    player_x: f32,
+    player_y: f32,
 }
 
 fn game_output_sound(
     _thread: &mut ThreadContext,
-    game_state: &mut GameState,
+    _game_state: &mut GameState,
     buffer: &mut GameSoundOutputBuffer,
-    tone_hz: u32,
+    _tone_hz: u32,
 ) {
-    let tone_volume = 3000;
-    let wave_period = buffer.samples_per_second / tone_hz;
+    // let tone_volume = 3000;
+    // let wave_period = buffer.samples_per_second / tone_hz;
 
-    unsafe {
-        let mut sample_out = buffer.samples;
-        for _sample_index in 0..buffer.sample_count {
-            // let sine_value = f32::sin(game_state.t_sine);
-            // let sample_value = (sine_value * tone_volume as f32) as i16;
-            let sample_value = 0;
-
-            // basically, we write L/R L/R L/R L/R etc.
-            // we use sample_out as an i16 ptr to the memory location we want to write to (region1 / ringbuffer)
-            *sample_out = sample_value;
-            sample_out = sample_out.offset(1);
-            *sample_out = sample_value;
-            sample_out = sample_out.offset(1);
-            // // move 1 sample worth forward
-            // game_state.t_sine += 2_f32 * f32::consts::PI * 1_f32 / wave_period as f32;
-            // if game_state.t_sine > 2_f32 * f32::consts::PI {
-            //     game_state.t_sine -= 2_f32 * f32::consts::PI;
-            // }
-        }
+    for sample_out in buffer.samples_mut() {
+        // let sine_value = f32::sin(game_state.t_sine);
+        // let sample_value = (sine_value * tone_volume as f32) as i16;
+        let sample_value = 0;
+
+        // basically, we write L/R L/R L/R L/R etc.
+        *sample_out = [sample_value, sample_value];
+        // // move 1 sample worth forward
+        // game_state.t_sine += 2_f32 * f32::consts::PI * 1_f32 / wave_period as f32;
+        // if game_state.t_sine > 2_f32 * f32::consts::PI {
+        //     game_state.t_sine -= 2_f32 * f32::consts::PI;
+        // }
     }
 }
 
+/// The GameState at the start of permanent storage.
+fn game_state(memory: &GameMemory) -> *mut GameState {
+    assert!(
+        size_of::<GameState>() <= memory.permanent_storage_size,
+        "GameState doesn't fit in permanent storage"
+    );
+    memory.permanent_storage.cast::<GameState>()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn game_get_api_hash() -> u64 {
+    GAME_API_HASH
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn game_update_and_render(
     thread: &mut ThreadContext,
@@ -51,14 +86,17 @@ pub extern "C" fn game_update_and_render(
     input: &mut GameInput,
     buffer: &mut GameOffscreenBuffer,
 ) {
-    debug_assert!(
-        size_of::<GameState>() <= memory.permanent_storage_size,
-        "GameState is too large for permanent storage"
-    );
-
-    let game_state = unsafe { &mut *memory.permanent_storage.cast::<GameState>() };
+    let game_state = unsafe { &mut *game_state(memory) };
 
     if !memory.is_initialized {
+        // NOTE(aalhendi): only the base and size are set. A --replay-state snapshot brings the arena's
+        //  used count along but may be loaded at a different address, fresh memory is all zero.
+        let arena = &mut game_state.permanent_arena;
+        arena.base = memory
+            .permanent_storage
+            .cast::<u8>()
+            .wrapping_add(size_of::<GameState>());
+        arena.size = memory.permanent_storage_size - size_of::<GameState>();
         // TODO(aalhendi): this may be more appropriate in to do in the platform layer
         memory.is_initialized = true;
     }
@@ -92,18 +130,65 @@ pub extern "C" fn game_update_and_render(
         }
     }
 
-    let tilemap: [[u32; 16]; 9] = [
-        [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
-        [0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
-        [0, 1, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
-        [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
-        [0, 1, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
-        [0, 1, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
-        [0, 0, 0, 0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
-        [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
-        [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0],
-    ];
+    // NOTE(aalhendi): the work list only has to last the frame, so it goes in the frame arena.
+    let render_work = memory
+        .frame_arena
+        .push_array::<RenderWork>(RENDER_BAND_COUNT);
+    let band_height = (buffer.height as usize).div_ceil(RENDER_BAND_COUNT) as i32;
+    let platform = &memory.platform;
+    for band_index in 0..RENDER_BAND_COUNT {
+        let band_top = (band_index as i32 * band_height).min(buffer.height);
+        let band_bottom = (band_top + band_height).min(buffer.height);
+        let band_memory = if band_top < band_bottom {
+            buffer.row_mut(band_top).as_mut_ptr().cast::<ffi::c_void>()
+        } else {
+            core::ptr::null_mut()
+        };
 
+        unsafe {
+            let work = render_work.add(band_index);
+            work.write(RenderWork {
+                buffer: GameOffscreenBuffer {
+                    memory: band_memory,
+                    width: if band_memory.is_null() {
+                        0
+                    } else {
+                        buffer.width
+                    },
+                    height: band_bottom - band_top,
+                    pitch: buffer.pitch,
+                    bytes_per_pixel: buffer.bytes_per_pixel,
+                },
+                band_top,
+                player_x: game_state.player_x,
+                player_y: game_state.player_y,
+            });
+            (platform.add_work_entry)(
+                platform.work_queue,
+                do_render_work,
+                work.cast::<ffi::c_void>(),
+            );
+        }
+    }
+    unsafe {
+        (platform.complete_all_work)(thread, platform.work_queue);
+    }
+}
+
+/// # Safety
+/// `data` must be a RenderWork that no other thread is using.
+unsafe extern "C" fn do_render_work(_thread: &mut ThreadContext, data: *mut ffi::c_void) {
+    let work = unsafe { &mut *data.cast::<RenderWork>() };
+    render_band(
+        &mut work.buffer,
+        work.band_top as f32,
+        work.player_x,
+        work.player_y,
+    );
+}
+
+/// Draws the frame into a band of the backbuffer that starts `band_top` rows down.
+fn render_band(buffer: &mut GameOffscreenBuffer, band_top: f32, player_x: f32, player_y: f32) {
     draw_rectangle(
         buffer,
         0.0,
@@ -118,15 +203,15 @@ pub extern "C" fn game_update_and_render(
     let tile_width = 60_f32;
     let tile_height = 60_f32;
 
-    for row in 0..9 {
-        for column in 0..16 {
-            let color = if tilemap[row][column] == 1 {
+    for (row, tile_row) in TILEMAP.iter().enumerate() {
+        for (column, &tile) in tile_row.iter().enumerate() {
+            let color = if tile == 1 {
                 (1.0, 1.0, 1.0)
             } else {
                 (0.5, 0.5, 0.5)
             };
             let min_x = column as f32 * tile_width;
-            let min_y = row as f32 * tile_height;
+            let min_y = row as f32 * tile_height - band_top;
             let max_x = min_x + tile_width;
             let max_y = min_y + tile_height;
             draw_rectangle(
@@ -138,8 +223,8 @@ pub extern "C" fn game_update_and_render(
     let player_rgb = (1.0, 1.0, 0.0);
     let player_width = 0.75 * tile_width;
     let player_height = 0.75 * tile_height;
-    let player_left = game_state.player_x - player_width / 2.0;
-    let player_top = game_state.player_y - player_height;
+    let player_left = player_x - player_width / 2.0;
+    let player_top = player_y - player_height - band_top;
     draw_rectangle(
         buffer,
         player_left,
@@ -161,10 +246,11 @@ pub extern "C" fn game_get_sound_samples(
     memory: &mut GameMemory,
     sound_buffer: &mut GameSoundOutputBuffer,
 ) {
-    let game_state = unsafe { &mut *memory.permanent_storage.cast::<GameState>() };
+    let game_state = unsafe { &mut *game_state(memory) };
     game_output_sound(thread, game_state, sound_buffer, 400);
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_rectangle(
     buffer: &mut GameOffscreenBuffer,
     f_min_x: f32,
@@ -188,21 +274,11 @@ fn draw_rectangle(
         | ((g * 255.0).round() as u32) << 8
         | ((b * 255.0).round() as u32);
 
-    let mut row = unsafe {
-        buffer
-            .memory
-            .cast::<u8>()
-            .add((min_x * buffer.bytes_per_pixel) as usize)
-            .offset((min_y * buffer.pitch) as isize)
-    };
-    for _y in min_y..max_y {
-        let mut pixel = row.cast::<u32>();
-        for _x in min_x..max_x {
-            unsafe {
-                *pixel = color;
-                pixel = pixel.add(1);
-            };
-        }
-        row = unsafe { row.add(buffer.pitch as usize) };
+    for row in buffer
+        .rows_mut()
+        .skip(min_y as usize)
+        .take((max_y - min_y) as usize)
+    {
+        row[min_x as usize..max_x as usize].fill(color);
     }
 }
@@ -0,0 +1,785 @@
+//! Runs the game without a window, sound device or input devices.
+//!
+//! Input comes from a script or a recorded `.hmi` stream instead of the keyboard, every frame gets
+//! the same fixed `dt_for_frame`, and frames/sound can be dumped to disk for inspection.
+//!
+//! With `--golden <dir>` it doubles as a regression check for `game_update_and_render`: the frames
+//! named by the reference images in `<dir>` (`frame_NNNNN.png` or `.ppm`) are compared against what
+//! the game renders, with `<dir>/script.txt` as the input. A frame fails when any channel of any
+//! pixel is off by more than `--tolerance`; the failing frame is written next to its reference as
+//! `frame_NNNNN.actual.png`, along with `frame_NNNNN.diff.png` which marks failing pixels in red.
+//! `--bless` renders the references instead of checking them.
+//!
+//! Scripts use the text form from `hm_replay`, so `hm-replay to-text` turns a recording into one.
+
+use core::{ffi, mem, ptr};
+use hm_replay::{REPLAY_DEFAULT_DT_FOR_FRAME, ReplayScript, replay_open_file, replay_read_frame};
+use interface::{
+    DEFAULT_FRAME_ARENA_SIZE, GameInput, GameMemory, GameOffscreenBuffer, GameSoundOutputBuffer,
+    REPLAY_EMPTY_MEMORY_HASH, ThreadContext, default_data_directory, gigabytes_to_bytes,
+    megabytes_to_bytes, replay_read_snapshot, split_transient_block,
+};
+use linux_common::{LinuxWavFileSink, linux_load_game_code_in_place, linux_platform_api};
+use std::{
+    ffi::CString,
+    fs::File,
+    io::{self, BufWriter, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("headless_platform can only be built on Linux.");
+
+pub const USAGE: &str = "\
+usage: hm --headless [options]
+  --game-lib <path>      game library to load (default: libhm.so next to the exe)
+  --frames <n>           number of frames to run (default: length of --replay)
+  --dt <seconds>         dt_for_frame for every frame (default: 1/30, or as recorded by --replay)
+  --size <w>x<h>         backbuffer size (default: 960x540)
+  --script <path>        drive input from a text script
+  --replay <path>        drive input from a recorded .hmi input stream
+  --replay-state <path>  start --replay from this memory snapshot (loop_edit_N_state.hmi)
+  --data-dir <path>      where the game's files are (default: data next to the exe)
+  --dump-dir <path>      write frames as PPM and all sound as sound.wav into this directory
+  --dump-every <n>       only dump every n-th frame (default: 1)
+  --golden <dir>         check frames against the references in <dir>, see the crate docs
+  --golden-frames <list> comma-separated frames to check (default: every reference in <dir>)
+  --tolerance <n>        allowed per-channel difference for each pixel (default: 0)
+  --bless                write the rendered frames as the new references instead of checking";
+
+const SAMPLES_PER_SECOND: u32 = 48000;
+
+pub struct HeadlessOptions {
+    pub game_lib: Option<PathBuf>,
+    pub frame_count: Option<u32>,
+    pub dt_for_frame: Option<f64>,
+    pub size: Option<(i32, i32)>,
+    pub script: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub replay_state: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+    pub dump_dir: Option<PathBuf>,
+    pub dump_every: u32,
+    pub golden_dir: Option<PathBuf>,
+    pub golden_frames: Option<Vec<u32>>,
+    pub tolerance: u8,
+    pub bless: bool,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            game_lib: None,
+            frame_count: None,
+            dt_for_frame: None,
+            size: None,
+            script: None,
+            replay: None,
+            replay_state: None,
+            data_dir: None,
+            dump_dir: None,
+            dump_every: 1,
+            golden_dir: None,
+            golden_frames: None,
+            tolerance: 0,
+            bless: false,
+        }
+    }
+}
+
+impl HeadlessOptions {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{arg} expects a value"));
+            match arg.as_str() {
+                "--game-lib" => options.game_lib = Some(PathBuf::from(value()?)),
+                "--frames" => options.frame_count = Some(parse_number(arg, value()?)?),
+                "--dt" => options.dt_for_frame = Some(parse_number(arg, value()?)?),
+                "--size" => {
+                    let size = value()?;
+                    let (width, height) = size
+                        .split_once('x')
+                        .ok_or_else(|| format!("--size expects <w>x<h>, got {size:?}"))?;
+                    options.size = Some((parse_number(arg, width)?, parse_number(arg, height)?));
+                }
+                "--script" => options.script = Some(PathBuf::from(value()?)),
+                "--replay" => options.replay = Some(PathBuf::from(value()?)),
+                "--replay-state" => options.replay_state = Some(PathBuf::from(value()?)),
+                "--data-dir" => options.data_dir = Some(PathBuf::from(value()?)),
+                "--dump-dir" => options.dump_dir = Some(PathBuf::from(value()?)),
+                "--dump-every" => options.dump_every = parse_number(arg, value()?)?,
+                "--golden" => options.golden_dir = Some(PathBuf::from(value()?)),
+                "--golden-frames" => {
+                    let mut frames = value()?
+                        .split(',')
+                        .map(|frame| parse_number(arg, frame.trim()))
+                        .collect::<Result<Vec<u32>, String>>()?;
+                    // NOTE(aalhendi): in order like the references from the directory, so the last
+                    //  one says how long to run.
+                    frames.sort_unstable();
+                    frames.dedup();
+                    options.golden_frames = Some(frames);
+                }
+                "--tolerance" => options.tolerance = parse_number(arg, value()?)?,
+                "--bless" => options.bless = true,
+                _ => return Err(format!("unknown option {arg:?}")),
+            }
+        }
+
+        if options.script.is_some() && options.replay.is_some() {
+            return Err("--script and --replay can't be used together".to_string());
+        }
+        if options.replay_state.is_some() && options.replay.is_none() {
+            return Err("--replay-state needs --replay".to_string());
+        }
+        if options.dt_for_frame.is_some_and(|dt| dt <= 0.0)
+            || options
+                .size
+                .is_some_and(|(width, height)| width <= 0 || height <= 0)
+        {
+            return Err("--dt and --size must be positive".to_string());
+        }
+        if options.golden_dir.is_none()
+            && (options.golden_frames.is_some() || options.tolerance != 0 || options.bless)
+        {
+            return Err("--golden-frames, --tolerance and --bless need --golden".to_string());
+        }
+        options.dump_every = options.dump_every.max(1);
+        Ok(options)
+    }
+}
+
+fn parse_number<T: core::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{arg}: {value:?} is not a valid number"))
+}
+
+/// Zeroed, read/write memory that is unmapped on drop.
+struct HeadlessMemory {
+    memory: *mut ffi::c_void,
+    size: usize,
+}
+
+impl HeadlessMemory {
+    fn allocate(size: usize) -> Result<Self, String> {
+        let memory = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if memory == libc::MAP_FAILED {
+            return Err(format!("Failed to allocate {size} bytes"));
+        }
+        Ok(Self { memory, size })
+    }
+}
+
+impl Drop for HeadlessMemory {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.memory, self.size);
+        }
+    }
+}
+
+enum HeadlessInputSource {
+    None,
+    Script {
+        script: ReplayScript,
+        next_event: usize,
+    },
+    Replay(io::BufReader<File>),
+}
+
+impl HeadlessInputSource {
+    /// Fills `new_input` for `frame`, carrying held buttons over from `old_input` like a real platform.
+    fn headless_next_input(
+        &mut self,
+        frame: u32,
+        old_input: &GameInput,
+        new_input: &mut GameInput,
+    ) -> Result<(), String> {
+        match self {
+            HeadlessInputSource::None => {}
+            HeadlessInputSource::Script { script, next_event } => {
+                script.next_input(next_event, frame, old_input, new_input);
+            }
+            HeadlessInputSource::Replay(file) => match replay_read_frame(file, new_input) {
+                Ok(()) => {}
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    // NOTE(aalhendi): the recording ran out, every frame after this gets no input.
+                    *new_input = GameInput::default();
+                    *self = HeadlessInputSource::None;
+                }
+                Err(e) => return Err(format!("Failed to read replay: {e}")),
+            },
+        }
+        Ok(())
+    }
+}
+
+/// A 0RGB image, the same layout as the backbuffer but tightly packed.
+struct HeadlessImage {
+    width: i32,
+    height: i32,
+    pixels: Vec<u32>,
+}
+
+impl HeadlessImage {
+    fn from_backbuffer(buffer: &GameOffscreenBuffer) -> Self {
+        let mut pixels = Vec::with_capacity((buffer.width * buffer.height) as usize);
+        for row in buffer.rows() {
+            // NOTE(aalhendi): the X byte is padding, the game doesn't promise anything about it.
+            pixels.extend(row.iter().map(|&pixel| pixel & 0x00FF_FFFF));
+        }
+        Self {
+            width: buffer.width,
+            height: buffer.height,
+            pixels,
+        }
+    }
+
+    fn from_rgb_rows<'a>(
+        width: i32,
+        height: i32,
+        rows: impl Iterator<Item = &'a [u8]>,
+        channels: usize,
+    ) -> Self {
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for row in rows.take(height as usize) {
+            pixels.extend(row.chunks_exact(channels).take(width as usize).map(
+                |pixel| match channels {
+                    1 | 2 => u32::from_be_bytes([0, pixel[0], pixel[0], pixel[0]]),
+                    _ => u32::from_be_bytes([0, pixel[0], pixel[1], pixel[2]]),
+                },
+            ));
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn read(path: &Path) -> Result<Self, String> {
+        let result = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("png") => Self::read_png(path),
+            Some("ppm") => std::fs::read(path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| Self::read_ppm(&bytes)),
+            _ => Err("expected a .png or .ppm file".to_string()),
+        };
+        result.map_err(|e| format!("Failed to read {}: {e}", path.display()))
+    }
+
+    fn read_png(path: &Path) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        let mut decoder = png::Decoder::new(io::BufReader::new(file));
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+        let mut data = vec![0; reader.output_buffer_size().ok_or("image is too large")?];
+        let info = reader.next_frame(&mut data).map_err(|e| e.to_string())?;
+
+        let channels = match info.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::Rgb => 3,
+            png::ColorType::Rgba => 4,
+            png::ColorType::Indexed => return Err("indexed color was not expanded".to_string()),
+        };
+        Ok(Self::from_rgb_rows(
+            info.width as i32,
+            info.height as i32,
+            data.chunks(info.line_size),
+            channels,
+        ))
+    }
+
+    /// Reads a binary PPM (P6) with a max value of 255.
+    fn read_ppm(bytes: &[u8]) -> Result<Self, String> {
+        let mut cursor = 0;
+        let mut next_token = || -> Option<&str> {
+            loop {
+                match bytes.get(cursor)? {
+                    b'#' => {
+                        while *bytes.get(cursor)? != b'\n' {
+                            cursor += 1;
+                        }
+                    }
+                    c if c.is_ascii_whitespace() => cursor += 1,
+                    _ => break,
+                }
+            }
+            let start = cursor;
+            while bytes.get(cursor).is_some_and(|c| !c.is_ascii_whitespace()) {
+                cursor += 1;
+            }
+            core::str::from_utf8(&bytes[start..cursor]).ok()
+        };
+
+        if next_token() != Some("P6") {
+            return Err("not a binary PPM (P6)".to_string());
+        }
+        let mut header_value = || {
+            next_token()
+                .and_then(|token| token.parse::<i32>().ok())
+                .ok_or("malformed PPM header")
+        };
+        let (width, height, max_value) = (header_value()?, header_value()?, header_value()?);
+        if max_value != 255 {
+            return Err("only 8-bit PPMs are supported".to_string());
+        }
+
+        // NOTE(aalhendi): exactly one whitespace byte separates the header from the pixels.
+        let data = bytes.get(cursor + 1..).unwrap_or_default();
+        let row_size = width as usize * 3;
+        if width <= 0 || height <= 0 || data.len() < row_size * height as usize {
+            return Err("PPM pixel data is truncated".to_string());
+        }
+        Ok(Self::from_rgb_rows(width, height, data.chunks(row_size), 3))
+    }
+
+    fn rgb_bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        self.pixels
+            .iter()
+            .flat_map(|&pixel| [(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8])
+    }
+
+    /// Writes a PNG or, for any other extension, a binary PPM (P6).
+    fn write(&self, path: &Path) -> Result<(), String> {
+        let result = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("png") => self.write_png(path),
+            _ => self.write_ppm(path).map_err(|e| e.to_string()),
+        };
+        result.map_err(|e| format!("Failed to write {}: {e}", path.display()))
+    }
+
+    fn write_ppm(&self, path: &Path) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        write!(file, "P6\n{} {}\n255\n", self.width, self.height)?;
+        let data: Vec<u8> = self.rgb_bytes().collect();
+        file.write_all(&data)?;
+        file.flush()
+    }
+
+    fn write_png(&self, path: &Path) -> Result<(), String> {
+        let file = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        let data: Vec<u8> = self.rgb_bytes().collect();
+        writer
+            .write_image_data(&data)
+            .and_then(|()| writer.finish())
+            .map_err(|e| e.to_string())
+    }
+}
+
+struct HeadlessGoldenFrame {
+    frame: u32,
+    path: PathBuf,
+    // NOTE(aalhendi): None when blessing, the rendered frame becomes the reference.
+    reference: Option<HeadlessImage>,
+}
+
+/// Finds the reference images in `dir`, or the ones for `frames` if they are given.
+fn headless_load_golden_frames(
+    dir: &Path,
+    frames: Option<&[u32]>,
+    bless: bool,
+) -> Result<Vec<HeadlessGoldenFrame>, String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {e}", dir.display()))?;
+    let mut references: Vec<(u32, PathBuf)> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            let frame = name
+                .strip_prefix("frame_")?
+                .strip_suffix(".png")
+                .or_else(|| name.strip_prefix("frame_")?.strip_suffix(".ppm"))?
+                .parse::<u32>()
+                .ok()?;
+            Some((frame, path))
+        })
+        .collect();
+    references.sort();
+
+    let frames: Vec<u32> = match frames {
+        Some(frames) => frames.to_vec(),
+        None => references.iter().map(|(frame, _)| *frame).collect(),
+    };
+    if frames.is_empty() {
+        return Err(format!(
+            "{} has no reference frames, use --bless with --golden-frames to create them",
+            dir.display()
+        ));
+    }
+
+    frames
+        .into_iter()
+        .map(|frame| {
+            let path = references
+                .iter()
+                .find(|(reference_frame, _)| *reference_frame == frame)
+                .map(|(_, path)| path.clone());
+            let reference = match (&path, bless) {
+                (_, true) => None,
+                (Some(path), false) => Some(HeadlessImage::read(path)?),
+                (None, false) => {
+                    return Err(format!(
+                        "{} has no reference for frame {frame}",
+                        dir.display()
+                    ));
+                }
+            };
+            Ok(HeadlessGoldenFrame {
+                frame,
+                path: path.unwrap_or_else(|| dir.join(format!("frame_{frame:05}.png"))),
+                reference,
+            })
+        })
+        .collect()
+}
+
+/// Compares `actual` against the reference, returning a description of the failure if it differs.
+fn headless_check_golden_frame(
+    golden_frame: &HeadlessGoldenFrame,
+    reference: &HeadlessImage,
+    actual: &HeadlessImage,
+    tolerance: u8,
+) -> Result<Option<String>, String> {
+    let name = golden_frame.path.display();
+    if (reference.width, reference.height) != (actual.width, actual.height) {
+        return Ok(Some(format!(
+            "{name}: reference is {}x{} but the backbuffer is {}x{}",
+            reference.width, reference.height, actual.width, actual.height
+        )));
+    }
+
+    let mut failing_pixel_count = 0;
+    let mut max_delta = 0;
+    let diff_pixels = reference
+        .pixels
+        .iter()
+        .zip(&actual.pixels)
+        .map(|(&expected, &pixel)| {
+            let delta = expected
+                .to_be_bytes()
+                .iter()
+                .zip(pixel.to_be_bytes())
+                .map(|(a, b)| a.abs_diff(b))
+                .max()
+                .unwrap_or_default();
+            max_delta = max_delta.max(delta);
+            if delta > tolerance {
+                failing_pixel_count += 1;
+                0x00FF_0000
+            } else {
+                // NOTE(aalhendi): matching pixels are a dimmed grey version of the reference so the
+                //  red stands out but you can still tell where in the frame it is.
+                let [_, r, g, b] = expected.to_be_bytes();
+                let grey = ((r as u32 + g as u32 + b as u32) / 3 / 4) as u8;
+                u32::from_be_bytes([0, grey, grey, grey])
+            }
+        })
+        .collect();
+
+    if failing_pixel_count == 0 {
+        return Ok(None);
+    }
+
+    let diff = HeadlessImage {
+        width: reference.width,
+        height: reference.height,
+        pixels: diff_pixels,
+    };
+    let diff_path = golden_frame.path.with_extension("diff.png");
+    let actual_path = golden_frame.path.with_extension("actual.png");
+    diff.write(&diff_path)?;
+    actual.write(&actual_path)?;
+    Ok(Some(format!(
+        "{name}: {failing_pixel_count} pixels differ by more than {tolerance} (max {max_delta}), see {}",
+        diff_path.display()
+    )))
+}
+
+fn headless_default_game_lib() -> Result<PathBuf, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("Failed to find the exe: {e}"))?;
+    Ok(exe.with_file_name("libhm.so"))
+}
+
+pub fn run(options: &HeadlessOptions) -> Result<(), String> {
+    let game_lib = match &options.game_lib {
+        Some(game_lib) => game_lib.clone(),
+        None => headless_default_game_lib()?,
+    };
+    let game_lib_name = CString::new(game_lib.as_os_str().as_bytes())
+        .map_err(|_| format!("{} is not a valid library path", game_lib.display()))?;
+    let game = linux_load_game_code_in_place(&game_lib_name)
+        .map_err(|e| format!("{e} ({})", game_lib.display()))?;
+
+    let golden_frames = match &options.golden_dir {
+        Some(golden_dir) => Some(headless_load_golden_frames(
+            golden_dir,
+            options.golden_frames.as_deref(),
+            options.bless,
+        )?),
+        None => None,
+    };
+
+    // NOTE(aalhendi): a golden case carries its own input unless told otherwise.
+    let script = options.script.clone().or_else(|| {
+        let golden_script = options.golden_dir.as_ref()?.join("script.txt");
+        (options.replay.is_none() && golden_script.exists()).then_some(golden_script)
+    });
+
+    let mut replay_header = None;
+    let mut input_source = match (&script, &options.replay) {
+        (Some(script), _) => {
+            let text = std::fs::read_to_string(script)
+                .map_err(|e| format!("Failed to read script {}: {e}", script.display()))?;
+            HeadlessInputSource::Script {
+                script: ReplayScript::parse(&text, &script.display().to_string())?,
+                next_event: 0,
+            }
+        }
+        (None, Some(replay)) => {
+            let (file, header) = replay_open_file(replay)?;
+            replay_header = Some(header);
+            HeadlessInputSource::Replay(io::BufReader::new(file))
+        }
+        (None, None) => HeadlessInputSource::None,
+    };
+
+    // NOTE(aalhendi): replays and scripts keep the dt they carry unless --dt overrides it, this is
+    //  for frames that don't have one.
+    let dt_for_frame = options
+        .dt_for_frame
+        .or(replay_header
+            .map(|header| header.dt_for_frame)
+            .filter(|dt| *dt > 0.0))
+        .unwrap_or(REPLAY_DEFAULT_DT_FOR_FRAME);
+
+    let script_frame_count = match &input_source {
+        HeadlessInputSource::Script { script, .. } => script.frame_count,
+        _ => None,
+    };
+    let frame_count = options
+        .frame_count
+        .or(replay_header.map(|header| header.frame_count))
+        .or(script_frame_count)
+        .or_else(|| Some(golden_frames.as_ref()?.last()?.frame + 1))
+        .ok_or("--frames is required unless the input or --golden says how long to run")?;
+    if let Some(golden_frame) = golden_frames
+        .iter()
+        .flatten()
+        .find(|golden_frame| golden_frame.frame >= frame_count)
+    {
+        return Err(format!(
+            "golden frame {} is past the last frame ({frame_count})",
+            golden_frame.frame
+        ));
+    }
+
+    // NOTE(aalhendi): golden references know what size they were rendered at.
+    let (width, height) = options
+        .size
+        .or_else(|| {
+            let reference = golden_frames
+                .iter()
+                .flatten()
+                .find_map(|f| f.reference.as_ref())?;
+            Some((reference.width, reference.height))
+        })
+        .unwrap_or((960, 540));
+
+    let bytes_per_pixel = 4;
+    let backbuffer_memory = HeadlessMemory::allocate((width * height * bytes_per_pixel) as usize)?;
+    let mut buffer = GameOffscreenBuffer {
+        memory: backbuffer_memory.memory,
+        width,
+        height,
+        pitch: width * bytes_per_pixel,
+        bytes_per_pixel,
+    };
+
+    let bytes_per_sample = size_of::<i16>() as u32 * 2;
+    let sound_memory = HeadlessMemory::allocate((SAMPLES_PER_SECOND * bytes_per_sample) as usize)?;
+    let samples = sound_memory.memory.cast::<i16>();
+
+    let permanent_storage_size = megabytes_to_bytes(64);
+    let transient_storage_size = gigabytes_to_bytes(1);
+    let frame_arena_size = DEFAULT_FRAME_ARENA_SIZE;
+    let data_directory = match &options.data_dir {
+        Some(data_dir) => data_dir.clone(),
+        None => default_data_directory()?,
+    };
+    let game_memory_block = HeadlessMemory::allocate(
+        permanent_storage_size + transient_storage_size + frame_arena_size,
+    )?;
+    let permanent_storage = game_memory_block.memory.cast::<()>();
+    let (transient_storage, frame_arena) = split_transient_block(
+        permanent_storage
+            .cast::<u8>()
+            .wrapping_add(permanent_storage_size),
+        transient_storage_size,
+        frame_arena_size,
+    );
+    let mut game_memory = GameMemory {
+        is_initialized: false,
+        permanent_storage_size,
+        transient_storage_size,
+        permanent_storage,
+        transient_storage,
+        frame_arena,
+        platform: linux_platform_api(data_directory),
+    };
+
+    // NOTE(aalhendi): fresh game memory is all zero, which hashes the same as an empty snapshot.
+    let mut memory_hash = REPLAY_EMPTY_MEMORY_HASH;
+    if let Some(replay_state) = &options.replay_state {
+        let memory = unsafe {
+            core::slice::from_raw_parts_mut(
+                game_memory_block.memory.cast::<u8>(),
+                game_memory_block.size,
+            )
+        };
+        memory_hash = replay_read_snapshot(replay_state, memory)?.memory_hash;
+    }
+    if let Some(replay_header) = replay_header
+        && replay_header.memory_hash != 0
+        && replay_header.memory_hash != memory_hash
+    {
+        return Err(
+            "The replay was recorded from a different memory snapshot, pass it with --replay-state"
+                .to_string(),
+        );
+    }
+
+    let mut wav_writer = match &options.dump_dir {
+        Some(dump_dir) => {
+            std::fs::create_dir_all(dump_dir)
+                .map_err(|e| format!("Failed to create {}: {e}", dump_dir.display()))?;
+            Some(
+                LinuxWavFileSink::create(
+                    dump_dir.join("sound.wav").as_os_str(),
+                    SAMPLES_PER_SECOND,
+                    bytes_per_sample,
+                )
+                .map_err(|e| format!("Failed to create sound.wav: {e}"))?,
+            )
+        }
+        None => None,
+    };
+
+    let mut new_input = GameInput::default();
+    let mut old_input = GameInput::default();
+    let mut thread_ctx = ThreadContext::default();
+    // NOTE(aalhendi): frames rarely line up with whole samples, this carries the fraction.
+    let mut frame_sample_remainder = 0_f64;
+    let mut golden_failures = Vec::with_capacity(golden_frames.as_ref().map_or(0, Vec::len));
+
+    for frame in 0..frame_count {
+        input_source.headless_next_input(frame, &old_input, &mut new_input)?;
+        if options.dt_for_frame.is_some() || new_input.dt_for_frame <= 0.0 {
+            new_input.dt_for_frame = dt_for_frame;
+        }
+
+        game_memory.frame_arena.reset();
+        if let Some(update_and_render) = game.update_and_render {
+            unsafe {
+                update_and_render(
+                    &mut thread_ctx,
+                    &mut game_memory,
+                    &mut new_input,
+                    &mut buffer,
+                );
+            }
+        }
+
+        let exact_samples =
+            SAMPLES_PER_SECOND as f64 * new_input.dt_for_frame + frame_sample_remainder;
+        let sample_count = exact_samples.floor();
+        frame_sample_remainder = exact_samples - sample_count;
+        let mut sound_buffer = GameSoundOutputBuffer {
+            samples_per_second: SAMPLES_PER_SECOND,
+            sample_count: (sample_count as u32).min(SAMPLES_PER_SECOND),
+            samples,
+        };
+        if let Some(get_sound_samples) = game.get_sound_samples {
+            unsafe {
+                get_sound_samples(&mut thread_ctx, &mut game_memory, &mut sound_buffer);
+            }
+        }
+
+        if let Some(dump_dir) = &options.dump_dir
+            && frame % options.dump_every == 0
+        {
+            HeadlessImage::from_backbuffer(&buffer)
+                .write(&dump_dir.join(format!("frame_{frame:05}.ppm")))?;
+        }
+        if let Some(golden_frame) = golden_frames
+            .iter()
+            .flatten()
+            .find(|golden_frame| golden_frame.frame == frame)
+        {
+            let image = HeadlessImage::from_backbuffer(&buffer);
+            match &golden_frame.reference {
+                Some(reference) => {
+                    if let Some(failure) = headless_check_golden_frame(
+                        golden_frame,
+                        reference,
+                        &image,
+                        options.tolerance,
+                    )? {
+                        golden_failures.push(failure);
+                    }
+                }
+                None => {
+                    image.write(&golden_frame.path)?;
+                    println!("Blessed {}", golden_frame.path.display());
+                }
+            }
+        }
+        if let Some(wav_writer) = &mut wav_writer {
+            wav_writer
+                .write_samples(&sound_buffer)
+                .map_err(|e| format!("Failed to write sound.wav: {e}"))?;
+        }
+
+        mem::swap(&mut new_input, &mut old_input);
+    }
+
+    if let Some(wav_writer) = wav_writer {
+        wav_writer
+            .finish()
+            .map_err(|e| format!("Failed to finish sound.wav: {e}"))?;
+    }
+
+    println!("Ran {frame_count} frames headless");
+    if let Some(golden_frames) = &golden_frames
+        && !options.bless
+    {
+        if !golden_failures.is_empty() {
+            return Err(format!(
+                "{} of {} golden frames differ:\n{}",
+                golden_failures.len(),
+                golden_frames.len(),
+                golden_failures.join("\n")
+            ));
+        }
+        println!("All {} golden frames match", golden_frames.len());
+    }
+    Ok(())
+}
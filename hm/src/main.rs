@@ -1,21 +1,80 @@
 mod platform {
+    use interface::{PLATFORM_USAGE, PlatformOptions};
+
+    fn parse_options(args: &[String]) -> PlatformOptions {
+        match PlatformOptions::parse(args) {
+            Ok(options) => options,
+            Err(e) => {
+                eprintln!("{e}\n\n{PLATFORM_USAGE}");
+                std::process::exit(1);
+            }
+        }
+    }
+
     #[cfg(target_os = "windows")]
-    pub fn run() {
-        win32_platform::run();
+    pub fn run(args: &[String]) {
+        if let Err(e) = win32_platform::run(&parse_options(args)) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
     }
 
     #[cfg(target_os = "linux")]
-    pub fn run() {
-        linux_platform::run();
+    pub fn run(args: &[String]) {
+        if let Err(e) = linux_platform::run(&parse_options(args)) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
     }
 
     #[cfg(not(any(target_os = "windows", target_os = "linux")))]
-    pub fn run() {
+    pub fn run(args: &[String]) {
+        let _ = parse_options(args);
         eprintln!("Unsupported platform. HM currently routes only Windows and Linux.");
         std::process::exit(1);
     }
+
+    #[cfg(target_os = "linux")]
+    pub fn run_headless(args: &[String]) {
+        if matches!(args, [arg] if arg == "--help" || arg == "-h") {
+            println!("{}", headless_platform::USAGE);
+            return;
+        }
+        let options = match headless_platform::HeadlessOptions::parse(args) {
+            Ok(options) => options,
+            Err(e) => {
+                eprintln!("{e}\n\n{}", headless_platform::USAGE);
+                std::process::exit(1);
+            }
+        };
+        if let Err(e) = headless_platform::run(&options) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn run_headless(_args: &[String]) {
+        eprintln!("--headless is currently only supported on Linux.");
+        std::process::exit(1);
+    }
 }
 
 fn main() {
-    platform::run();
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // NOTE(aalhendi): --headless picks the platform, so it can go anywhere and the rest are its options.
+    if let Some(headless_index) = args.iter().position(|arg| arg == "--headless") {
+        args.remove(headless_index);
+        platform::run_headless(&args);
+        return;
+    }
+    match args.first().map(String::as_str) {
+        Some("--help" | "-h") => println!("{}", interface::PLATFORM_USAGE),
+        Some("--help-bindings") => println!(
+            "{}\n\n{}",
+            interface::BINDINGS_FORMAT,
+            interface::DEFAULT_BINDINGS
+        ),
+        _ => platform::run(&args),
+    }
 }
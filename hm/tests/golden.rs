@@ -0,0 +1,42 @@
+// NOTE(aalhendi): Runs the checked-in golden scripts through `hm --headless`. The game is a cdylib
+// the binary loads at runtime, so it gets built here next to hm_app, where the platform looks for
+// it by default.
+#![cfg(target_os = "linux")]
+
+use std::{path::Path, process::Command};
+
+fn build_game_lib() {
+    let mut cargo = Command::new(env!("CARGO"));
+    cargo.args(["build", "-p", "game"]);
+    if !cfg!(debug_assertions) {
+        cargo.arg("--release");
+    }
+    let status = cargo.status().expect("Failed to run cargo");
+    assert!(status.success(), "Failed to build the game library");
+}
+
+fn run_golden(name: &str) -> String {
+    build_game_lib();
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../game/golden")
+        .join(name);
+    let output = Command::new(env!("CARGO_BIN_EXE_hm_app"))
+        .arg("--headless")
+        .arg("--golden")
+        .arg(&golden_dir)
+        .output()
+        .expect("Failed to run hm_app");
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    assert!(
+        output.status.success(),
+        "golden {name} failed:\n{stdout}{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    stdout
+}
+
+#[test]
+fn walk_matches_golden_frames() {
+    let stdout = run_golden("walk");
+    assert!(stdout.contains("All 4 golden frames match"), "{stdout}");
+}
@@ -0,0 +1,284 @@
+use hm_replay::{REPLAY_DEFAULT_DT_FOR_FRAME, ReplayScript, replay_read_file, replay_write_file};
+use interface::{GameButton, GameButtonState, GameInput, ReplayHeader};
+use std::{
+    io::{self, Write},
+    path::Path,
+};
+
+const USAGE: &str = "\
+usage: hm-replay <command> [arguments]
+  inspect <in.hmi> [--changes]      print every frame's transitions, sticks and mouse state
+                                    (--changes skips frames where nothing happened)
+  to-text <in.hmi> [<out.txt>]      write a recording as an editable script (default: stdout)
+  from-text <in.txt> <out.hmi>      turn a script back into a recording
+  trim <in.hmi> <out.hmi> <first> <last> [--allow-desync]
+                                    keep frames first..=last. Playback starts from the game's
+                                    state at frame 0, so a first past 0 needs --allow-desync
+  help                              print this and the script format";
+
+const SCRIPT_FORMAT: &str = "\
+script format, one event per line, # starts a comment:
+  <frame> dt <seconds>
+  <frame> button <controller> <GameButton> down|up
+  <frame> stick <controller> left|right <x> <y>
+  <frame> controller <controller> connected|disconnected|analog|digital
+  <frame> mouse <x> <y>
+  <frame> mouse_button <0-4> down|up
+  <frame> wheel <notches>
+  frames <count>
+  memory_hash <hex>
+unmentioned state carries over from the previous frame, wheel only lasts for its frame.
+frames before the first dt line run at 1/30 seconds.";
+
+const MOUSE_BUTTON_NAMES: [&str; 5] = ["Left", "Middle", "Right", "X1", "X2"];
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args[..] {
+        ["inspect", path] => inspect(Path::new(path), false),
+        ["inspect", path, "--changes"] => inspect(Path::new(path), true),
+        ["to-text", path] => to_text(Path::new(path), None),
+        ["to-text", path, out_path] => to_text(Path::new(path), Some(Path::new(out_path))),
+        ["from-text", path, out_path] => from_text(Path::new(path), Path::new(out_path)),
+        ["trim", path, out_path, first, last] => trim_command(path, out_path, first, last, false),
+        ["trim", path, out_path, first, last, "--allow-desync"] => {
+            trim_command(path, out_path, first, last, true)
+        }
+        ["help"] | ["--help"] | ["-h"] => {
+            println!("{USAGE}\n\n{SCRIPT_FORMAT}");
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+fn describe_header(path: &Path, header: &ReplayHeader) -> String {
+    let mut description = format!(
+        "{}: version {}, {} frames, {} byte GameInput",
+        path.display(),
+        header.version,
+        header.frame_count,
+        header.game_input_size
+    );
+    if header.dt_for_frame > 0.0 {
+        description += &format!(
+            ", dt {:.6} ({:.1} Hz)",
+            header.dt_for_frame,
+            1.0 / header.dt_for_frame
+        );
+    }
+    if header.memory_hash != 0 {
+        description += &format!(", memory hash {:#018x}", header.memory_hash);
+    }
+    description
+}
+
+/// `Name v2` for a button that went down twice this frame and ended down, `Name ^1` for one release.
+fn describe_transition(name: &str, state: &GameButtonState) -> Option<String> {
+    if state.half_transition_count == 0 {
+        return None;
+    }
+    let direction = if state.ended_down { 'v' } else { '^' };
+    Some(format!("{name} {direction}{}", state.half_transition_count))
+}
+
+fn inspect(path: &Path, changes_only: bool) -> Result<(), String> {
+    let (header, frames) = replay_read_file(path)?;
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let write_error = |e: io::Error| e.to_string();
+    writeln!(out, "{}", describe_header(path, &header)).map_err(write_error)?;
+
+    let default_input = GameInput::default();
+    for (frame, input) in frames.iter().enumerate() {
+        let old_input = match frame {
+            0 => &default_input,
+            _ => &frames[frame - 1],
+        };
+
+        let held_mouse_buttons: Vec<&str> = input
+            .mouse_buttons
+            .iter()
+            .zip(MOUSE_BUTTON_NAMES)
+            .filter(|(state, _)| state.ended_down)
+            .map(|(_, name)| name)
+            .collect();
+        let mut lines: Vec<String> = input
+            .mouse_buttons
+            .iter()
+            .zip(MOUSE_BUTTON_NAMES)
+            .filter_map(|(state, name)| describe_transition(name, state))
+            .collect();
+        let mouse_changed = (input.mouse_x, input.mouse_y)
+            != (old_input.mouse_x, old_input.mouse_y)
+            || input.mouse_z != 0
+            || !lines.is_empty();
+        if !lines.is_empty() {
+            lines = vec![format!("  mouse  {}", lines.join("  "))];
+        }
+
+        for (controller_index, controller) in input.controllers.iter().enumerate() {
+            let old_controller = &old_input.controllers[controller_index];
+            if !controller.is_connected && !old_controller.is_connected {
+                continue;
+            }
+
+            let transitions: Vec<String> = GameButton::ALL
+                .into_iter()
+                .filter_map(|button| describe_transition(button.name(), controller.button(button)))
+                .collect();
+            let sticks = [
+                controller.left_stick_average_x,
+                controller.left_stick_average_y,
+                controller.right_stick_average_x,
+                controller.right_stick_average_y,
+            ];
+            let old_sticks = [
+                old_controller.left_stick_average_x,
+                old_controller.left_stick_average_y,
+                old_controller.right_stick_average_x,
+                old_controller.right_stick_average_y,
+            ];
+            let connection_changed = controller.is_connected != old_controller.is_connected
+                || controller.is_analog != old_controller.is_analog;
+            let sticks_changed = sticks != old_sticks;
+            if changes_only && transitions.is_empty() && !sticks_changed && !connection_changed {
+                continue;
+            }
+
+            let mut line = format!("  c{controller_index}");
+            if connection_changed || !changes_only {
+                line += match (controller.is_connected, controller.is_analog) {
+                    (false, _) => "  disconnected",
+                    (true, true) => "  analog",
+                    (true, false) => "  digital",
+                };
+            }
+            if sticks_changed || (controller.is_analog && !changes_only) {
+                line += &format!(
+                    "  left ({:.3}, {:.3})  right ({:.3}, {:.3})",
+                    sticks[0], sticks[1], sticks[2], sticks[3]
+                );
+            }
+            for transition in transitions {
+                line += "  ";
+                line += &transition;
+            }
+            lines.push(line);
+        }
+
+        if changes_only && lines.is_empty() && !mouse_changed {
+            continue;
+        }
+        writeln!(
+            out,
+            "frame {frame:5}  dt {:.6}  mouse ({}, {}) wheel {}  held [{}]",
+            input.dt_for_frame,
+            input.mouse_x,
+            input.mouse_y,
+            input.mouse_z,
+            held_mouse_buttons.join(" ")
+        )
+        .map_err(write_error)?;
+        for line in lines {
+            writeln!(out, "{line}").map_err(write_error)?;
+        }
+    }
+    out.flush().map_err(write_error)
+}
+
+fn to_text(path: &Path, out_path: Option<&Path>) -> Result<(), String> {
+    let (header, frames) = replay_read_file(path)?;
+    let script = ReplayScript::from_frames(&frames, header.memory_hash);
+    let result = match out_path {
+        Some(out_path) => std::fs::File::create(out_path)
+            .map(io::BufWriter::new)
+            .and_then(|mut out| {
+                script.write(&mut out)?;
+                out.flush()
+            }),
+        None => script.write(&mut io::stdout().lock()),
+    };
+    result.map_err(|e| format!("Failed to write the script: {e}"))
+}
+
+fn from_text(path: &Path, out_path: &Path) -> Result<(), String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let script = ReplayScript::parse(&text, &path.display().to_string())?;
+    let mut frames = script.to_frames();
+    // NOTE(aalhendi): same as headless without --dt, frames before the first dt line get the default.
+    for input in frames
+        .iter_mut()
+        .take_while(|input| input.dt_for_frame <= 0.0)
+    {
+        input.dt_for_frame = REPLAY_DEFAULT_DT_FOR_FRAME;
+    }
+    let dt_for_frame = frames
+        .first()
+        .map_or(REPLAY_DEFAULT_DT_FOR_FRAME, |input| input.dt_for_frame);
+    replay_write_file(out_path, dt_for_frame, script.memory_hash, &frames)?;
+    println!("Wrote {} frames to {}", frames.len(), out_path.display());
+    Ok(())
+}
+
+fn trim_command(
+    path: &str,
+    out_path: &str,
+    first: &str,
+    last: &str,
+    allow_desync: bool,
+) -> Result<(), String> {
+    match (first.parse(), last.parse()) {
+        (Ok(first), Ok(last)) => trim(
+            Path::new(path),
+            Path::new(out_path),
+            first,
+            last,
+            allow_desync,
+        ),
+        _ => Err("trim expects frame numbers".to_string()),
+    }
+}
+
+fn trim(
+    path: &Path,
+    out_path: &Path,
+    first: usize,
+    last: usize,
+    allow_desync: bool,
+) -> Result<(), String> {
+    let (header, frames) = replay_read_file(path)?;
+    if first > last || last >= frames.len() {
+        return Err(format!(
+            "{}..={} is outside the {} frames in {}",
+            first,
+            last,
+            frames.len(),
+            path.display()
+        ));
+    }
+
+    // NOTE(aalhendi): the recording starts from the game's state at frame 0 and there is no snapshot
+    //  of the state at any later frame. Cutting frames off the front means playback goes somewhere
+    //  else, so it has to be asked for, and the trimmed file can't claim the old memory hash.
+    if first > 0 && !allow_desync {
+        return Err(format!(
+            "Trimming off frames before {first} desyncs playback from the game's state, \
+             pass --allow-desync to do it anyway"
+        ));
+    }
+    let memory_hash = if first == 0 { header.memory_hash } else { 0 };
+    if memory_hash != header.memory_hash {
+        println!("Note: the trimmed recording no longer starts from its memory snapshot");
+    }
+    let frames = &frames[first..=last];
+    replay_write_file(out_path, frames[0].dt_for_frame, memory_hash, frames)?;
+    println!("Wrote {} frames to {}", frames.len(), out_path.display());
+    Ok(())
+}
@@ -20,6 +20,103 @@ pub struct GameSoundOutputBuffer {
     pub samples: *mut i16,
 }
 
+// NOTE(aalhendi): the accessors below trust the platform to hand over `height` rows of `pitch`
+//  bytes, each starting with `width` u32 pixels, and `sample_count` stereo pairs of samples.
+impl GameOffscreenBuffer {
+    /// Row `y` of the buffer, `width` pixels long.
+    pub fn row(&self, y: i32) -> &[u32] {
+        assert!(
+            (0..self.height).contains(&y),
+            "Row {y} is outside the buffer"
+        );
+        unsafe { core::slice::from_raw_parts(self.row_ptr(y), self.width as usize) }
+    }
+
+    /// Row `y` of the buffer, `width` pixels long.
+    pub fn row_mut(&mut self, y: i32) -> &mut [u32] {
+        assert!(
+            (0..self.height).contains(&y),
+            "Row {y} is outside the buffer"
+        );
+        unsafe { core::slice::from_raw_parts_mut(self.row_ptr(y), self.width as usize) }
+    }
+
+    /// Every row from top to bottom, skipping the padding `pitch` leaves at the end of each.
+    pub fn rows(&self) -> impl Iterator<Item = &[u32]> {
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    /// Every row from top to bottom, skipping the padding `pitch` leaves at the end of each.
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [u32]> {
+        let width = self.width as usize;
+        (0..self.height).map(move |y| {
+            // NOTE(aalhendi): pitch is at least a row of pixels, so no two rows overlap.
+            unsafe { core::slice::from_raw_parts_mut(self.row_ptr(y), width) }
+        })
+    }
+
+    /// The pixel at (`x`, `y`), None if that is outside the buffer.
+    pub fn pixel(&self, x: i32, y: i32) -> Option<u32> {
+        if !(0..self.height).contains(&y) {
+            return None;
+        }
+        self.row(y).get(usize::try_from(x).ok()?).copied()
+    }
+
+    /// Sets the pixel at (`x`, `y`), pixels outside the buffer are clipped away.
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: u32) {
+        if (0..self.width).contains(&x) && (0..self.height).contains(&y) {
+            self.row_mut(y)[x as usize] = color;
+        }
+    }
+
+    fn row_ptr(&self, y: i32) -> *mut u32 {
+        assert!(self.bytes_per_pixel == 4, "Pixels must be 32 bits wide");
+        assert!(
+            self.width >= 0 && self.height >= 0,
+            "Buffer size can't be negative"
+        );
+        assert!(
+            self.width
+                .checked_mul(self.bytes_per_pixel)
+                .is_some_and(|row_size| self.pitch >= row_size)
+                && self.pitch % 4 == 0,
+            "Rows must be aligned and can't overlap"
+        );
+        if self.memory.is_null() {
+            // NOTE(aalhendi): from_raw_parts wants a non-null pointer even for nothing.
+            assert!(self.width == 0, "Backbuffer has no memory");
+            return core::ptr::NonNull::dangling().as_ptr();
+        }
+        unsafe {
+            self.memory
+                .cast::<u8>()
+                .add(y as usize * self.pitch as usize)
+                .cast::<u32>()
+        }
+    }
+}
+
+impl GameSoundOutputBuffer {
+    /// The samples to fill, one `[left, right]` pair per sample.
+    pub fn samples(&self) -> &[[i16; 2]] {
+        unsafe { core::slice::from_raw_parts(self.samples_ptr(), self.sample_count as usize) }
+    }
+
+    /// The samples to fill, one `[left, right]` pair per sample.
+    pub fn samples_mut(&mut self) -> &mut [[i16; 2]] {
+        unsafe { core::slice::from_raw_parts_mut(self.samples_ptr(), self.sample_count as usize) }
+    }
+
+    fn samples_ptr(&self) -> *mut [i16; 2] {
+        if self.samples.is_null() {
+            assert!(self.sample_count == 0, "Sound buffer has no memory");
+            return core::ptr::NonNull::dangling().as_ptr();
+        }
+        self.samples.cast::<[i16; 2]>()
+    }
+}
+
 #[repr(C)]
 pub struct GameMemory {
     pub is_initialized: bool,
@@ -29,58 +126,159 @@ pub struct GameMemory {
     pub transient_storage_size: usize,
     // NOTE(aalhendi): REQUIRED to be cleared to 0 at startup
     pub transient_storage: *mut (),
+    /// Scratch for the current frame, set aside by the platform right after transient storage and
+    /// reset before every frame. Nothing in here lives to see the next frame.
+    pub frame_arena: MemoryArena,
 
-    // NOTE(aalhendi): The platform layer will fill these in
-    #[cfg(feature = "internal_build")]
-    pub debug_platform_read_entire_file: DebugPlatformReadEntireFileFn,
-    #[cfg(feature = "internal_build")]
-    pub debug_platform_write_entire_file: DebugPlatformWriteEntireFileFn,
-    #[cfg(feature = "internal_build")]
-    pub debug_platform_free_file_memory: DebugPlatformFreeFileMemoryFn,
+    // NOTE(aalhendi): The platform layer will fill this in
+    pub platform: PlatformApi,
 }
 
-impl Default for GameMemory {
-    fn default() -> Self {
-        #[cfg(feature = "internal_build")]
-        unsafe extern "C" fn default_read_file(
-            _thread_context: &mut ThreadContext,
-            _filename: *const core::ffi::c_char,
-        ) -> DebugPlatformReadFileResult {
-            unimplemented!("default_read_file is not implemented for this platform")
-        }
+/// How big `GameMemory::frame_arena` is unless the platform is told otherwise.
+pub const DEFAULT_FRAME_ARENA_SIZE: usize = megabytes_to_bytes(16);
 
-        #[cfg(feature = "internal_build")]
-        unsafe extern "C" fn default_write_file(
-            _thread_context: &mut ThreadContext,
-            _filename: *const core::ffi::c_char,
-            _memory_size: u32,
-            _memory: *mut core::ffi::c_void,
-        ) -> bool {
-            unimplemented!("default_write_file is not implemented for this platform")
-        }
+/// Lays out the block the platform allocates after permanent storage: `transient_storage_size`
+/// bytes of transient storage, then `frame_arena_size` bytes of frame arena. Returns where transient
+/// storage starts and the frame arena.
+pub fn split_transient_block(
+    block: *mut u8,
+    transient_storage_size: usize,
+    frame_arena_size: usize,
+) -> (*mut (), MemoryArena) {
+    let frame_arena =
+        MemoryArena::new(block.wrapping_add(transient_storage_size), frame_arena_size);
+    (block.cast::<()>(), frame_arena)
+}
 
-        #[cfg(feature = "internal_build")]
-        unsafe extern "C" fn default_free_memory(
-            _thread_context: &mut ThreadContext,
-            _memory: *mut core::ffi::c_void,
-        ) {
-            unimplemented!("default_free_memory is not implemented for this platform")
-        }
+/// A bump allocator over a block of memory. Nothing pushed is freed on its own, the whole arena goes
+/// away at once. Pushed memory isn't cleared: permanent and transient storage start out zeroed, but
+/// memory an arena hands out a second time doesn't.
+#[derive(Debug)]
+#[repr(C)]
+pub struct MemoryArena {
+    pub base: *mut u8,
+    pub size: usize,
+    pub used: usize,
+    /// How many TemporaryMemory scopes are open on the arena.
+    pub temp_count: u32,
+}
+
+/// A checkpoint of an arena. Everything pushed after it goes away at end_temporary_memory.
+#[must_use = "the arena isn't rolled back until end_temporary_memory"]
+pub struct TemporaryMemory {
+    used: usize,
+    // NOTE(aalhendi): the arena's temp_count before this scope began, so 0 is the outermost.
+    index: u32,
+    #[cfg(debug_assertions)]
+    arena_base: *mut u8,
+}
 
+impl MemoryArena {
+    pub fn new(base: *mut u8, size: usize) -> Self {
         Self {
-            is_initialized: false,
-            permanent_storage_size: 0,
-            permanent_storage: core::ptr::null_mut(),
-            transient_storage_size: 0,
-            transient_storage: core::ptr::null_mut(),
-            #[cfg(feature = "internal_build")]
-            debug_platform_read_entire_file: default_read_file,
-            #[cfg(feature = "internal_build")]
-            debug_platform_write_entire_file: default_write_file,
-            #[cfg(feature = "internal_build")]
-            debug_platform_free_file_memory: default_free_memory,
+            base,
+            size,
+            used: 0,
+            temp_count: 0,
         }
     }
+
+    pub fn begin_temporary_memory(&mut self) -> TemporaryMemory {
+        let temp = TemporaryMemory {
+            used: self.used,
+            index: self.temp_count,
+            #[cfg(debug_assertions)]
+            arena_base: self.base,
+        };
+        self.temp_count += 1;
+        temp
+    }
+
+    /// Rolls the arena back to where `temp` began. Scopes end innermost first.
+    pub fn end_temporary_memory(&mut self, temp: TemporaryMemory) {
+        #[cfg(debug_assertions)]
+        debug_assert!(
+            temp.arena_base == self.base,
+            "Temporary memory ended on a different arena than it began on"
+        );
+        debug_assert!(
+            temp.index + 1 == self.temp_count,
+            "Temporary memory scope {} ended while scope {} is still open",
+            temp.index,
+            self.temp_count.wrapping_sub(1)
+        );
+        debug_assert!(
+            temp.used <= self.used,
+            "The arena was rolled back past this temporary memory scope"
+        );
+        self.used = temp.used;
+        self.temp_count = self.temp_count.saturating_sub(1);
+    }
+
+    /// Asserts that every temporary memory scope on the arena has ended.
+    pub fn check_arena(&self) {
+        debug_assert!(
+            self.temp_count == 0,
+            "{} temporary memory scopes are still open",
+            self.temp_count
+        );
+    }
+
+    /// Throws away everything pushed so far.
+    pub fn reset(&mut self) {
+        self.check_arena();
+        self.used = 0;
+    }
+
+    /// Bytes left, not counting the padding the next push may need.
+    pub fn remaining(&self) -> usize {
+        self.size - self.used
+    }
+
+    /// `size` bytes at a multiple of `alignment`, which must be a power of two.
+    pub fn push_size(&mut self, size: usize, alignment: usize) -> *mut u8 {
+        assert!(
+            alignment.is_power_of_two(),
+            "Alignment {alignment} is not a power of two"
+        );
+        // NOTE(aalhendi): aligned by address, the base itself doesn't have to be aligned.
+        let next = (self.base as usize).wrapping_add(self.used);
+        let alignment_offset = next.wrapping_neg() & (alignment - 1);
+        let new_used = self
+            .used
+            .checked_add(alignment_offset)
+            .and_then(|used| used.checked_add(size))
+            .filter(|&used| used <= self.size);
+        let Some(new_used) = new_used else {
+            panic!(
+                "Arena overflow: {size} bytes aligned to {alignment} don't fit, {} of {} bytes used",
+                self.used, self.size
+            );
+        };
+
+        let result = self.base.wrapping_add(self.used + alignment_offset);
+        self.used = new_used;
+        result
+    }
+
+    pub fn push_struct<T>(&mut self) -> *mut T {
+        self.push_size(size_of::<T>(), align_of::<T>()).cast::<T>()
+    }
+
+    pub fn push_array<T>(&mut self, count: usize) -> *mut T {
+        let Some(size) = size_of::<T>().checked_mul(count) else {
+            panic!(
+                "Arena overflow: {count} elements of {} bytes",
+                size_of::<T>()
+            );
+        };
+        self.push_size(size, align_of::<T>()).cast::<T>()
+    }
+
+    /// Carves `size` bytes out of this arena as an arena of their own.
+    pub fn sub_arena(&mut self, size: usize, alignment: usize) -> MemoryArena {
+        MemoryArena::new(self.push_size(size, alignment), size)
+    }
 }
 
 pub type GameUpdateAndRenderFn = unsafe extern "C" fn(
@@ -96,32 +294,174 @@ pub type GameGetSoundSamplesFn = unsafe extern "C" fn(
     sound_buffer: &mut GameSoundOutputBuffer,
 );
 
-#[cfg(feature = "internal_build")]
+/// Bump this whenever a function is added to `PlatformApi` or one changes what it does.
+pub const PLATFORM_API_VERSION: u32 = 4;
+
+/// Everything the game can ask of the platform. The platform fills it in before the first frame and
+/// it doesn't change after that.
 #[repr(C)]
-pub struct DebugPlatformReadFileResult {
+pub struct PlatformApi {
+    /// The PLATFORM_API_VERSION the platform was built with.
+    pub version: u32,
+
+    pub log: PlatformLogFn,
+
+    pub get_wall_clock: PlatformGetWallClockFn,
+    pub get_seconds_elapsed: PlatformGetSecondsElapsedFn,
+    pub read_cycle_counter: PlatformReadCycleCounterFn,
+
+    pub read_entire_file: PlatformReadEntireFileFn,
+    pub write_entire_file: PlatformWriteEntireFileFn,
+    pub free_file_memory: PlatformFreeFileMemoryFn,
+
+    pub file_system: *mut PlatformFileSystem,
+    pub get_all_files_of_type_begin: PlatformGetAllFilesOfTypeBeginFn,
+    pub get_all_files_of_type_end: PlatformGetAllFilesOfTypeEndFn,
+    pub open_next_file: PlatformOpenNextFileFn,
+    pub open_file: PlatformOpenFileFn,
+    pub read_data_from_file: PlatformReadDataFromFileFn,
+    pub file_error: PlatformFileErrorFn,
+    pub close_file: PlatformCloseFileFn,
+
+    pub allocate_memory: PlatformAllocateMemoryFn,
+    pub deallocate_memory: PlatformDeallocateMemoryFn,
+
+    pub work_queue: *mut PlatformWorkQueue,
+    pub add_work_entry: PlatformAddWorkEntryFn,
+    pub complete_all_work: PlatformCompleteAllWorkFn,
+}
+
+/// Prints `message_size` bytes of UTF-8 from `message` as a line of the platform's log.
+pub type PlatformLogFn = unsafe extern "C" fn(message: *const u8, message_size: usize);
+
+/// A timestamp in platform-specific ticks, only meaningful to get_seconds_elapsed.
+pub type PlatformGetWallClockFn = extern "C" fn() -> u64;
+pub type PlatformGetSecondsElapsedFn = extern "C" fn(start: u64, end: u64) -> f32;
+/// The CPU's cycle counter, for profiling. Cycles don't convert to time.
+pub type PlatformReadCycleCounterFn = extern "C" fn() -> u64;
+
+#[repr(C)]
+pub struct PlatformReadFileResult {
     pub memory: *mut core::ffi::c_void,
-    // NOTE(aalhendi): we are limited to u32 because of the Windows API... Its debug anyway.
+    // NOTE(aalhendi): we are limited to u32 because of the Windows API.
     pub size: u32,
 }
 
-#[cfg(feature = "internal_build")]
-pub type DebugPlatformReadEntireFileFn = unsafe extern "C" fn(
+pub type PlatformReadEntireFileFn = unsafe extern "C" fn(
     thread_context: &mut ThreadContext,
     filename: *const core::ffi::c_char,
-) -> DebugPlatformReadFileResult;
+) -> PlatformReadFileResult;
 
-#[cfg(feature = "internal_build")]
-pub type DebugPlatformWriteEntireFileFn = unsafe extern "C" fn(
+pub type PlatformWriteEntireFileFn = unsafe extern "C" fn(
     thread_context: &mut ThreadContext,
     filename: *const core::ffi::c_char,
     memory_size: u32,
     memory: *mut core::ffi::c_void,
 ) -> bool;
 
-#[cfg(feature = "internal_build")]
-pub type DebugPlatformFreeFileMemoryFn =
+pub type PlatformFreeFileMemoryFn =
     unsafe extern "C" fn(thread_context: &mut ThreadContext, memory: *mut core::ffi::c_void);
 
+// NOTE(aalhendi): the handle-based file functions work on any thread, so the game can stream data in
+//  from work queue entries. A handle or group must only be used by one thread at a time.
+
+/// The kinds of file the game can list in the data directory, told apart by their extension.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub enum PlatformFileType {
+    Asset,
+    SavedGame,
+}
+
+impl PlatformFileType {
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Asset => "hma",
+            Self::SavedGame => "hms",
+        }
+    }
+}
+
+/// An open file. Errors stick to the handle: once one is reported, reads only zero their destination,
+/// so the game can check `has_errors` after a whole batch of reads instead of after each one.
+#[repr(C)]
+pub struct PlatformFileHandle {
+    pub has_errors: bool,
+    // NOTE(aalhendi): the fd or HANDLE, only the platform looks at it.
+    pub platform: usize,
+}
+
+/// The files of one type, opened one at a time with open_next_file.
+#[repr(C)]
+pub struct PlatformFileGroup {
+    pub file_count: u32,
+    pub platform: *mut core::ffi::c_void,
+}
+
+/// Only the platform knows what is in here, the game just passes it back.
+#[repr(C)]
+pub struct PlatformFileSystem {
+    _private: [u8; 0],
+}
+
+/// Lists the files of `file_type` in the data directory. A missing directory is an empty group.
+pub type PlatformGetAllFilesOfTypeBeginFn = unsafe extern "C" fn(
+    file_system: *mut PlatformFileSystem,
+    file_type: PlatformFileType,
+) -> PlatformFileGroup;
+/// Releases the group. The files opened from it stay open.
+pub type PlatformGetAllFilesOfTypeEndFn = unsafe extern "C" fn(group: &mut PlatformFileGroup);
+/// Opens the group's next file. Past the last one, or if it can't be opened, the handle has errors.
+pub type PlatformOpenNextFileFn =
+    unsafe extern "C" fn(group: &mut PlatformFileGroup) -> PlatformFileHandle;
+/// Opens `filename`, relative to the data directory, for reading.
+pub type PlatformOpenFileFn = unsafe extern "C" fn(
+    file_system: *mut PlatformFileSystem,
+    filename: *const core::ffi::c_char,
+) -> PlatformFileHandle;
+/// Reads `size` bytes starting `offset` bytes into the file into `dest`. Running into the end of the
+/// file is an error.
+pub type PlatformReadDataFromFileFn = unsafe extern "C" fn(
+    handle: &mut PlatformFileHandle,
+    offset: u64,
+    size: u64,
+    dest: *mut core::ffi::c_void,
+);
+/// Marks the handle as having errors and logs `message_size` bytes of UTF-8 from `message`. For the
+/// game to report files it can't make sense of.
+pub type PlatformFileErrorFn =
+    unsafe extern "C" fn(handle: &mut PlatformFileHandle, message: *const u8, message_size: usize);
+pub type PlatformCloseFileFn = unsafe extern "C" fn(handle: &mut PlatformFileHandle);
+
+/// `size` bytes of zeroed memory straight from the OS, or null if there isn't enough.
+pub type PlatformAllocateMemoryFn = extern "C" fn(size: usize) -> *mut core::ffi::c_void;
+/// Hands back memory from allocate_memory, `size` being what was asked for.
+pub type PlatformDeallocateMemoryFn =
+    unsafe extern "C" fn(memory: *mut core::ffi::c_void, size: usize);
+
+/// Only the platform knows what is in here, the game just passes it back.
+#[repr(C)]
+pub struct PlatformWorkQueue {
+    _private: [u8; 0],
+}
+
+pub type PlatformWorkQueueCallback =
+    unsafe extern "C" fn(thread_context: &mut ThreadContext, data: *mut core::ffi::c_void);
+
+/// Queues `callback(data)` to run on one of the worker threads, or on the main thread while it is in
+/// complete_all_work. Only the main thread may add work, and `data` must stay alive until
+/// complete_all_work returns.
+pub type PlatformAddWorkEntryFn = unsafe extern "C" fn(
+    queue: *mut PlatformWorkQueue,
+    callback: PlatformWorkQueueCallback,
+    data: *mut core::ffi::c_void,
+);
+
+/// Helps with everything added to `queue` so far and returns once all of it has run.
+pub type PlatformCompleteAllWorkFn =
+    unsafe extern "C" fn(thread_context: &mut ThreadContext, queue: *mut PlatformWorkQueue);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 #[repr(C)]
 pub enum GameButton {
     MoveUp = 0,
@@ -141,6 +481,48 @@ pub enum GameButton {
     Back,
 }
 
+impl GameButton {
+    pub const ALL: [GameButton; 12] = [
+        GameButton::MoveUp,
+        GameButton::MoveDown,
+        GameButton::MoveLeft,
+        GameButton::MoveRight,
+        GameButton::ActionUp,
+        GameButton::ActionDown,
+        GameButton::ActionLeft,
+        GameButton::ActionRight,
+        GameButton::RightShoulder,
+        GameButton::LeftShoulder,
+        GameButton::Start,
+        GameButton::Back,
+    ];
+
+    /// The variant name, as used in input scripts and config files.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            GameButton::MoveUp => "MoveUp",
+            GameButton::MoveDown => "MoveDown",
+            GameButton::MoveLeft => "MoveLeft",
+            GameButton::MoveRight => "MoveRight",
+            GameButton::ActionUp => "ActionUp",
+            GameButton::ActionDown => "ActionDown",
+            GameButton::ActionLeft => "ActionLeft",
+            GameButton::ActionRight => "ActionRight",
+            GameButton::RightShoulder => "RightShoulder",
+            GameButton::LeftShoulder => "LeftShoulder",
+            GameButton::Start => "Start",
+            GameButton::Back => "Back",
+        }
+    }
+
+    /// The inverse of [`GameButton::name`].
+    pub fn from_name(name: &str) -> Option<GameButton> {
+        GameButton::ALL
+            .into_iter()
+            .find(|button| button.name() == name)
+    }
+}
+
 #[derive(Default)]
 #[repr(C)]
 pub struct GameButtonState {
@@ -181,6 +563,8 @@ impl GameControllerInput {
 #[repr(C)]
 pub struct GameInput {
     pub mouse_buttons: [GameButtonState; 5],
+    // NOTE(aalhendi): mouse_x/mouse_y are in backbuffer pixels. mouse_z is how many wheel notches
+    //  were scrolled this frame, positive is away from the user.
     pub mouse_x: i32,
     pub mouse_y: i32,
     pub mouse_z: i32,
@@ -208,15 +592,1200 @@ pub const fn terabytes_to_bytes(terabytes: usize) -> usize {
     terabytes * 1024 * 1024 * 1024 * 1024
 }
 
-#[cfg(feature = "internal_build")]
 #[inline(always)]
 pub fn safe_truncate_i64_to_u32(value: i64) -> u32 {
     debug_assert!(value < u32::MAX as i64, "Value is too large");
     value as u32
 }
 
+/// Whatever the game needs to know about the thread it is being called on.
 #[derive(Default)]
 #[repr(C)]
 pub struct ThreadContext {
-    placeholder: u32,
+    /// 0 on the main thread, 1 and up on the work queue's worker threads.
+    pub thread_index: u32,
+}
+
+/// Bump this when something shared with the game changes meaning without changing layout, e.g. a
+/// field is reinterpreted or a function starts expecting something different.
+pub const GAME_API_VERSION: u32 = 2;
+
+/// GAME_API_VERSION and the layout of everything passed between the platform and the game. The
+/// game exports it as `game_get_api_hash` and the platform won't run a game whose hash differs.
+pub const GAME_API_HASH: u64 = game_api_hash();
+
+pub type GameGetApiHashFn = unsafe extern "C" fn() -> u64;
+
+const fn game_api_hash() -> u64 {
+    use core::mem::{align_of, offset_of, size_of};
+
+    let layout = [
+        GAME_API_VERSION as usize,
+        size_of::<GameOffscreenBuffer>(),
+        align_of::<GameOffscreenBuffer>(),
+        offset_of!(GameOffscreenBuffer, memory),
+        offset_of!(GameOffscreenBuffer, width),
+        offset_of!(GameOffscreenBuffer, height),
+        offset_of!(GameOffscreenBuffer, pitch),
+        offset_of!(GameOffscreenBuffer, bytes_per_pixel),
+        size_of::<GameSoundOutputBuffer>(),
+        align_of::<GameSoundOutputBuffer>(),
+        offset_of!(GameSoundOutputBuffer, samples_per_second),
+        offset_of!(GameSoundOutputBuffer, sample_count),
+        offset_of!(GameSoundOutputBuffer, samples),
+        size_of::<GameMemory>(),
+        align_of::<GameMemory>(),
+        offset_of!(GameMemory, is_initialized),
+        offset_of!(GameMemory, permanent_storage_size),
+        offset_of!(GameMemory, permanent_storage),
+        offset_of!(GameMemory, transient_storage_size),
+        offset_of!(GameMemory, transient_storage),
+        offset_of!(GameMemory, frame_arena),
+        size_of::<MemoryArena>(),
+        offset_of!(MemoryArena, base),
+        offset_of!(MemoryArena, size),
+        offset_of!(MemoryArena, used),
+        offset_of!(MemoryArena, temp_count),
+        offset_of!(GameMemory, platform),
+        PLATFORM_API_VERSION as usize,
+        size_of::<PlatformApi>(),
+        size_of::<PlatformReadFileResult>(),
+        offset_of!(PlatformReadFileResult, memory),
+        offset_of!(PlatformReadFileResult, size),
+        size_of::<PlatformFileType>(),
+        size_of::<PlatformFileHandle>(),
+        offset_of!(PlatformFileHandle, has_errors),
+        offset_of!(PlatformFileHandle, platform),
+        size_of::<PlatformFileGroup>(),
+        offset_of!(PlatformFileGroup, file_count),
+        offset_of!(PlatformFileGroup, platform),
+        size_of::<GameButtonState>(),
+        align_of::<GameButtonState>(),
+        offset_of!(GameButtonState, half_transition_count),
+        offset_of!(GameButtonState, ended_down),
+        size_of::<GameControllerInput>(),
+        align_of::<GameControllerInput>(),
+        offset_of!(GameControllerInput, is_connected),
+        offset_of!(GameControllerInput, is_analog),
+        offset_of!(GameControllerInput, left_stick_average_x),
+        offset_of!(GameControllerInput, left_stick_average_y),
+        offset_of!(GameControllerInput, right_stick_average_x),
+        offset_of!(GameControllerInput, right_stick_average_y),
+        offset_of!(GameControllerInput, buttons),
+        size_of::<GameInput>(),
+        align_of::<GameInput>(),
+        offset_of!(GameInput, mouse_buttons),
+        offset_of!(GameInput, mouse_x),
+        offset_of!(GameInput, mouse_y),
+        offset_of!(GameInput, mouse_z),
+        offset_of!(GameInput, dt_for_frame),
+        offset_of!(GameInput, controllers),
+        size_of::<ThreadContext>(),
+        align_of::<ThreadContext>(),
+    ];
+
+    const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+    // NOTE(aalhendi): a while loop because for loops aren't allowed in const fns yet.
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut i = 0;
+    while i < layout.len() {
+        hash = (hash ^ layout[i] as u64).wrapping_mul(FNV_PRIME);
+        i += 1;
+    }
+    hash
+}
+
+/// Why a game library that exports `get_api_hash` (or None if it doesn't) can't be run.
+pub fn game_api_check(get_api_hash: Option<GameGetApiHashFn>) -> Result<(), String> {
+    let Some(get_api_hash) = get_api_hash else {
+        return Err("it doesn't export game_get_api_hash, it predates the API check".to_string());
+    };
+    let api_hash = unsafe { get_api_hash() };
+    if api_hash != GAME_API_HASH {
+        return Err(format!(
+            "it was built against game API {api_hash:#018x} but the platform expects \
+             {GAME_API_HASH:#018x}, rebuild both from the same interface"
+        ));
+    }
+    Ok(())
+}
+
+pub const PLATFORM_USAGE: &str = "\
+usage: hm [options]
+       hm --headless [options]      run without a window, hm --headless --help lists its options
+  --game-lib <path>              game library to load and hot reload (default: next to the exe)
+  --window-size <w>x<h>          initial size of the window's client area (default: backbuffer size)
+  --backbuffer-size <w>x<h>      resolution the game renders at (default: 960x540)
+  --permanent-storage <size>     bytes, or with a K, M or G suffix (default: 64M)
+  --transient-storage <size>     bytes, or with a K, M or G suffix (default: 1G)
+  --frame-arena <size>           per-frame scratch on top of transient storage (default: 16M)
+  --paused                       start paused, P unpauses (internal builds)
+  --replay <path>                loop a recorded .hmi input stream from the first frame
+  --replay-state <path>          start --replay from this memory snapshot (loop_edit_N_state.hmi)
+  --frames <n>                   exit after running n frames (not with --paused)
+  --bindings <path>              key and gamepad bindings (default: hm_bindings.txt next to the exe
+                                 if there is one), hm --help-bindings prints the format
+  --data-dir <path>              where the game's files are (default: data next to the exe)";
+
+/// Startup settings for the windowed platforms, parsed from the command line.
+pub struct PlatformOptions {
+    pub game_lib: Option<std::path::PathBuf>,
+    // NOTE(aalhendi): None means the window starts the same size as the backbuffer.
+    pub window_size: Option<(i32, i32)>,
+    pub backbuffer_size: (i32, i32),
+    pub permanent_storage_size: usize,
+    pub transient_storage_size: usize,
+    pub frame_arena_size: usize,
+    pub start_paused: bool,
+    pub replay: Option<std::path::PathBuf>,
+    pub replay_state: Option<std::path::PathBuf>,
+    pub frame_count: Option<u32>,
+    pub bindings: Option<std::path::PathBuf>,
+    pub data_dir: Option<std::path::PathBuf>,
+}
+
+impl Default for PlatformOptions {
+    fn default() -> Self {
+        Self {
+            game_lib: None,
+            window_size: None,
+            backbuffer_size: (960, 540),
+            permanent_storage_size: megabytes_to_bytes(64),
+            transient_storage_size: gigabytes_to_bytes(1),
+            frame_arena_size: DEFAULT_FRAME_ARENA_SIZE,
+            start_paused: false,
+            replay: None,
+            replay_state: None,
+            frame_count: None,
+            bindings: None,
+            data_dir: None,
+        }
+    }
+}
+
+impl PlatformOptions {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{arg} expects a value"));
+            match arg.as_str() {
+                "--game-lib" => options.game_lib = Some(value()?.into()),
+                "--window-size" => options.window_size = Some(parse_option_size(arg, value()?)?),
+                "--backbuffer-size" => options.backbuffer_size = parse_option_size(arg, value()?)?,
+                "--permanent-storage" => {
+                    options.permanent_storage_size = parse_option_bytes(arg, value()?)?;
+                }
+                "--transient-storage" => {
+                    options.transient_storage_size = parse_option_bytes(arg, value()?)?;
+                }
+                "--frame-arena" => options.frame_arena_size = parse_option_bytes(arg, value()?)?,
+                "--paused" => options.start_paused = true,
+                "--replay" => options.replay = Some(value()?.into()),
+                "--replay-state" => options.replay_state = Some(value()?.into()),
+                "--frames" => options.frame_count = Some(parse_option_number(arg, value()?)?),
+                "--bindings" => options.bindings = Some(value()?.into()),
+                "--data-dir" => options.data_dir = Some(value()?.into()),
+                _ => return Err(format!("unknown option {arg:?}")),
+            }
+        }
+
+        if options.replay_state.is_some() && options.replay.is_none() {
+            return Err("--replay-state needs --replay".to_string());
+        }
+        // NOTE(aalhendi): --frames only counts frames the game runs, so a paused start would never
+        // reach the count.
+        if options.start_paused && options.frame_count.is_some() {
+            return Err("--paused can't be combined with --frames".to_string());
+        }
+        Ok(options)
+    }
+
+    /// Loads `--bindings`, or `BINDINGS_FILE_NAME` next to the exe. Without either there is nothing
+    /// to load and the defaults are used.
+    pub fn load_bindings(&self) -> Result<InputBindings, String> {
+        if let Some(path) = &self.bindings {
+            return InputBindings::load(path);
+        }
+        let exe_path = std::env::current_exe().map_err(|e| e.to_string())?;
+        match exe_path.parent().map(|dir| dir.join(BINDINGS_FILE_NAME)) {
+            Some(path) if path.exists() => InputBindings::load(&path),
+            _ => Ok(InputBindings::default()),
+        }
+    }
+
+    /// `--data-dir`, or `DATA_DIRECTORY_NAME` next to the exe.
+    pub fn data_directory(&self) -> Result<std::path::PathBuf, String> {
+        match &self.data_dir {
+            Some(data_dir) => Ok(data_dir.clone()),
+            None => default_data_directory(),
+        }
+    }
+}
+
+pub const DATA_DIRECTORY_NAME: &str = "data";
+
+/// `DATA_DIRECTORY_NAME` next to the exe. It doesn't have to exist.
+pub fn default_data_directory() -> Result<std::path::PathBuf, String> {
+    let exe_path = std::env::current_exe().map_err(|e| e.to_string())?;
+    Ok(exe_path.with_file_name(DATA_DIRECTORY_NAME))
+}
+
+fn parse_option_number<T: core::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{arg}: {value:?} is not a valid number"))
+}
+
+/// `<w>x<h>`, both positive.
+fn parse_option_size(arg: &str, value: &str) -> Result<(i32, i32), String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or_else(|| format!("{arg} expects <w>x<h>, got {value:?}"))?;
+    let size = (
+        parse_option_number(arg, width)?,
+        parse_option_number(arg, height)?,
+    );
+    if size.0 <= 0 || size.1 <= 0 {
+        return Err(format!("{arg} must be positive, got {value:?}"));
+    }
+    Ok(size)
+}
+
+/// A byte count like `4096`, `512K`, `64M` or `1G`, not zero.
+fn parse_option_bytes(arg: &str, value: &str) -> Result<usize, String> {
+    let (number, unit) = match value.as_bytes().last() {
+        Some(b'K' | b'k') => (&value[..value.len() - 1], 1024),
+        Some(b'M' | b'm') => (&value[..value.len() - 1], megabytes_to_bytes(1)),
+        Some(b'G' | b'g') => (&value[..value.len() - 1], gigabytes_to_bytes(1)),
+        _ => (value, 1),
+    };
+    parse_option_number::<usize>(arg, number)?
+        .checked_mul(unit)
+        .filter(|&bytes| bytes > 0)
+        .ok_or_else(|| format!("{arg}: {value:?} is not a valid size"))
+}
+
+pub const BINDINGS_FILE_NAME: &str = "hm_bindings.txt";
+
+pub const BINDINGS_FORMAT: &str = "\
+bindings format, one binding per line, # starts a comment:
+  key <key> <GameButton>
+  gamepad <button> <GameButton>
+keys are A-Z, 0-9, F1-F12, Up, Down, Left, Right, Space, Enter, Escape, Tab, Backspace, Shift,
+Control and Alt. Letters are what the key types in the active layout, so AZERTY players want Z
+and Q where QWERTY has W and A. Digits are the number row, whatever it types.
+gamepad buttons are A, B, X, Y, LeftShoulder, RightShoulder, Start and Back, named like an Xbox
+pad's. The d-pad and left stick always drive MoveUp/Down/Left/Right.
+a file replaces all the defaults, so anything it doesn't bind is unbound. The defaults are:";
+
+/// The bindings used when there is no bindings file, in the bindings file format.
+pub const DEFAULT_BINDINGS: &str = "\
+key W MoveUp
+key S MoveDown
+key A MoveLeft
+key D MoveRight
+key Up ActionUp
+key Down ActionDown
+key Left ActionLeft
+key Right ActionRight
+key Q LeftShoulder
+key E RightShoulder
+key Escape Start
+key Space Back
+gamepad A ActionDown
+gamepad B ActionRight
+gamepad X ActionLeft
+gamepad Y ActionUp
+gamepad LeftShoulder LeftShoulder
+gamepad RightShoulder RightShoulder
+gamepad Start Start
+gamepad Back Back
+";
+
+/// A keyboard key as bindings name it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputKey {
+    /// `A`-`Z` or `0`-`9`, stored uppercase.
+    Character(u8),
+    /// `F1`-`F12`.
+    Function(u8),
+    Up,
+    Down,
+    Left,
+    Right,
+    Space,
+    Enter,
+    Escape,
+    Tab,
+    Backspace,
+    Shift,
+    Control,
+    Alt,
+}
+
+impl InputKey {
+    const NAMED: [(InputKey, &'static str); 12] = [
+        (InputKey::Up, "Up"),
+        (InputKey::Down, "Down"),
+        (InputKey::Left, "Left"),
+        (InputKey::Right, "Right"),
+        (InputKey::Space, "Space"),
+        (InputKey::Enter, "Enter"),
+        (InputKey::Escape, "Escape"),
+        (InputKey::Tab, "Tab"),
+        (InputKey::Backspace, "Backspace"),
+        (InputKey::Shift, "Shift"),
+        (InputKey::Control, "Control"),
+        (InputKey::Alt, "Alt"),
+    ];
+    // NOTE(aalhendi): 26 letters, 10 digits, 12 function keys, then the named ones.
+    const COUNT: usize = 26 + 10 + 12 + Self::NAMED.len();
+
+    /// The key that types `c`, if it is one bindings can name. Lowercase letters are fine.
+    pub fn from_char(c: char) -> Option<InputKey> {
+        let c = c.to_ascii_uppercase();
+        (c.is_ascii_uppercase() || c.is_ascii_digit()).then_some(InputKey::Character(c as u8))
+    }
+
+    pub fn from_name(name: &str) -> Option<InputKey> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Self::from_char(c);
+        }
+        if let Some(number) = name.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+            return (1..=12)
+                .contains(&number)
+                .then_some(InputKey::Function(number));
+        }
+        Self::NAMED
+            .into_iter()
+            .find(|(_, key_name)| *key_name == name)
+            .map(|(key, _)| key)
+    }
+
+    /// Where the key lives in `InputBindings::keys`, None for keys bindings can't name.
+    fn index(self) -> Option<usize> {
+        match self {
+            InputKey::Character(c @ b'A'..=b'Z') => Some((c - b'A') as usize),
+            InputKey::Character(c @ b'0'..=b'9') => Some(26 + (c - b'0') as usize),
+            InputKey::Function(number @ 1..=12) => Some(36 + (number - 1) as usize),
+            InputKey::Character(_) | InputKey::Function(_) => None,
+            key => Self::NAMED
+                .iter()
+                .position(|(named_key, _)| *named_key == key)
+                .map(|position| 48 + position),
+        }
+    }
+}
+
+/// A gamepad's digital buttons, named like an Xbox pad's.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GamepadButton {
+    A = 0,
+    B,
+    X,
+    Y,
+    LeftShoulder,
+    RightShoulder,
+    Start,
+    Back,
+}
+
+impl GamepadButton {
+    pub const ALL: [GamepadButton; 8] = [
+        GamepadButton::A,
+        GamepadButton::B,
+        GamepadButton::X,
+        GamepadButton::Y,
+        GamepadButton::LeftShoulder,
+        GamepadButton::RightShoulder,
+        GamepadButton::Start,
+        GamepadButton::Back,
+    ];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            GamepadButton::A => "A",
+            GamepadButton::B => "B",
+            GamepadButton::X => "X",
+            GamepadButton::Y => "Y",
+            GamepadButton::LeftShoulder => "LeftShoulder",
+            GamepadButton::RightShoulder => "RightShoulder",
+            GamepadButton::Start => "Start",
+            GamepadButton::Back => "Back",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<GamepadButton> {
+        GamepadButton::ALL
+            .into_iter()
+            .find(|button| button.name() == name)
+    }
+}
+
+/// Which `GameButton` each key and gamepad button presses. Several keys or gamepad buttons can
+/// press the same `GameButton`.
+#[derive(Clone, Copy)]
+pub struct InputBindings {
+    keys: [Option<GameButton>; InputKey::COUNT],
+    gamepad_buttons: [Option<GameButton>; GamepadButton::ALL.len()],
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self::parse(DEFAULT_BINDINGS, "default bindings").expect("Default bindings don't parse")
+    }
+}
+
+impl InputBindings {
+    pub fn key(&self, key: InputKey) -> Option<GameButton> {
+        self.keys[key.index()?]
+    }
+
+    pub fn gamepad_button(&self, button: GamepadButton) -> Option<GameButton> {
+        self.gamepad_buttons[button as usize]
+    }
+
+    /// Whether any of the gamepad buttons bound to `button` is held, asking `is_down` about each.
+    pub fn is_gamepad_button_down(
+        &self,
+        button: GameButton,
+        is_down: impl Fn(GamepadButton) -> bool,
+    ) -> bool {
+        GamepadButton::ALL.into_iter().any(|gamepad_button| {
+            self.gamepad_button(gamepad_button) == Some(button) && is_down(gamepad_button)
+        })
+    }
+
+    pub fn parse(text: &str, source_name: &str) -> Result<Self, String> {
+        let mut bindings = Self {
+            keys: [None; InputKey::COUNT],
+            gamepad_buttons: [None; GamepadButton::ALL.len()],
+        };
+
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut fields = line.split_whitespace();
+            let (kind, name, button) = match (fields.next(), fields.next(), fields.next()) {
+                (None, ..) => continue,
+                (Some(kind), Some(name), Some(button)) if fields.next().is_none() => {
+                    (kind, name, button)
+                }
+                _ => {
+                    return Err(format!(
+                        "{source_name}:{}: expected three fields",
+                        line_index + 1
+                    ));
+                }
+            };
+
+            let error = |message: &str| format!("{source_name}:{}: {message}", line_index + 1);
+            let button =
+                GameButton::from_name(button).ok_or_else(|| error("unknown GameButton"))?;
+            let binding = match kind {
+                "key" => InputKey::from_name(name)
+                    .and_then(InputKey::index)
+                    .map(|index| &mut bindings.keys[index])
+                    .ok_or_else(|| error("unknown key"))?,
+                "gamepad" => GamepadButton::from_name(name)
+                    .map(|gamepad_button| &mut bindings.gamepad_buttons[gamepad_button as usize])
+                    .ok_or_else(|| error("unknown gamepad button"))?,
+                _ => return Err(error("expected key or gamepad")),
+            };
+            if let Some(bound_button) = *binding
+                && bound_button != button
+            {
+                return Err(error(&format!(
+                    "{name} is already bound to {}",
+                    bound_button.name()
+                )));
+            }
+            *binding = Some(button);
+        }
+        Ok(bindings)
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        Self::parse(&text, &path.display().to_string())
+    }
+}
+
+/// "HMIR" as it appears at the start of the file.
+pub const REPLAY_MAGIC: u32 = u32::from_le_bytes(*b"HMIR");
+pub const REPLAY_VERSION: u32 = 1;
+
+/// Starts every `loop_edit_N_input.hmi`, followed by `frame_count` raw `GameInput`s.
+/// All fields are stored little endian.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+pub struct ReplayHeader {
+    pub magic: u32,
+    pub version: u32,
+    pub game_input_size: u32,
+    pub frame_count: u32,
+    pub dt_for_frame: f64,
+    // NOTE(aalhendi): replay_hash_memory() of the snapshot the recording starts from, 0 if unknown.
+    pub memory_hash: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReplayHeaderError {
+    NotAReplay,
+    UnsupportedVersion(u32),
+    GameInputSizeMismatch { found: u32 },
+    FrameCountMismatch { header: u32, stream: u64 },
+}
+
+impl core::fmt::Display for ReplayHeaderError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ReplayHeaderError::NotAReplay => write!(f, "not a replay input stream"),
+            ReplayHeaderError::UnsupportedVersion(version) => write!(
+                f,
+                "replay version {version} is not supported (expected {REPLAY_VERSION})"
+            ),
+            ReplayHeaderError::GameInputSizeMismatch { found } => write!(
+                f,
+                "replay was recorded with a {found} byte GameInput, this build's is {}",
+                size_of::<GameInput>()
+            ),
+            ReplayHeaderError::FrameCountMismatch { header, stream } => write!(
+                f,
+                "replay header says {header} frames but the stream holds {stream}"
+            ),
+        }
+    }
+}
+
+impl ReplayHeader {
+    pub const SIZE: usize = size_of::<ReplayHeader>();
+
+    /// A header for a recording that hasn't written any frames yet.
+    pub fn new(dt_for_frame: f64, memory_hash: u64) -> Self {
+        Self {
+            magic: REPLAY_MAGIC,
+            version: REPLAY_VERSION,
+            game_input_size: size_of::<GameInput>() as u32,
+            frame_count: 0,
+            dt_for_frame,
+            memory_hash,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0_u8; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.magic.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.version.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.game_input_size.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.frame_count.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.dt_for_frame.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.memory_hash.to_le_bytes());
+        bytes
+    }
+
+    /// Validates the start of an input stream that is `stream_size` bytes long in total.
+    ///
+    /// Headerless streams from before this format existed are refused: nothing says which
+    /// `GameInput` layout they were recorded with, so they'd decode as garbage.
+    /// A frame count of 0 means the recording never finished, and the stream length is trusted.
+    pub fn parse(prefix: &[u8], stream_size: u64) -> Result<Self, ReplayHeaderError> {
+        let game_input_size = size_of::<GameInput>() as u64;
+        let read_u32 = |offset: usize| replay_read_u32(prefix, offset);
+        let read_u64 = |offset: usize| replay_read_u64(prefix, offset);
+
+        if prefix.len() < Self::SIZE || read_u32(0) != REPLAY_MAGIC {
+            return Err(ReplayHeaderError::NotAReplay);
+        }
+
+        let mut header = Self {
+            magic: read_u32(0),
+            version: read_u32(4),
+            game_input_size: read_u32(8),
+            frame_count: read_u32(12),
+            dt_for_frame: f64::from_bits(read_u64(16)),
+            memory_hash: read_u64(24),
+        };
+        if header.version != REPLAY_VERSION {
+            return Err(ReplayHeaderError::UnsupportedVersion(header.version));
+        }
+        if header.game_input_size as u64 != game_input_size {
+            return Err(ReplayHeaderError::GameInputSizeMismatch {
+                found: header.game_input_size,
+            });
+        }
+
+        let stream_frame_count = stream_size.saturating_sub(Self::SIZE as u64) / game_input_size;
+        if header.frame_count == 0 {
+            header.frame_count = stream_frame_count as u32;
+        } else if header.frame_count as u64 != stream_frame_count {
+            return Err(ReplayHeaderError::FrameCountMismatch {
+                header: header.frame_count,
+                stream: stream_frame_count,
+            });
+        }
+        Ok(header)
+    }
+
+    /// Where the first `GameInput` starts in the stream.
+    pub fn data_offset(&self) -> u64 {
+        Self::SIZE as u64
+    }
+}
+
+fn replay_read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+fn replay_read_u64(bytes: &[u8], offset: usize) -> u64 {
+    (replay_read_u32(bytes, offset) as u64) | ((replay_read_u32(bytes, offset + 4) as u64) << 32)
+}
+
+/// Snapshots are stored and hashed in pages of this size, whatever the OS page size is.
+pub const REPLAY_PAGE_SIZE: usize = 4096;
+/// "HMIS" as it appears at the start of the file.
+pub const REPLAY_SNAPSHOT_MAGIC: u32 = u32::from_le_bytes(*b"HMIS");
+pub const REPLAY_SNAPSHOT_VERSION: u32 = 1;
+
+/// Starts every `loop_edit_N_state.hmi`. It is followed by `page_count` u32 page indices in
+/// increasing order, then the `REPLAY_PAGE_SIZE` bytes of each of those pages. Pages that aren't
+/// listed are zero. All fields are stored little endian.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(C)]
+pub struct ReplaySnapshotHeader {
+    pub magic: u32,
+    pub version: u32,
+    pub page_size: u32,
+    pub page_count: u32,
+    pub memory_size: u64,
+    // NOTE(aalhendi): replay_hash_memory() of the memory the snapshot holds.
+    pub memory_hash: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ReplaySnapshotError {
+    NotASnapshot,
+    UnsupportedVersion(u32),
+    PageSizeMismatch { found: u32 },
+}
+
+impl core::fmt::Display for ReplaySnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ReplaySnapshotError::NotASnapshot => write!(f, "not a replay memory snapshot"),
+            ReplaySnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "snapshot version {version} is not supported (expected {REPLAY_SNAPSHOT_VERSION})"
+            ),
+            ReplaySnapshotError::PageSizeMismatch { found } => write!(
+                f,
+                "snapshot was taken in {found} byte pages, this build uses {REPLAY_PAGE_SIZE}"
+            ),
+        }
+    }
+}
+
+impl ReplaySnapshotHeader {
+    pub const SIZE: usize = size_of::<ReplaySnapshotHeader>();
+
+    pub fn new(memory_size: u64, page_count: u32, memory_hash: u64) -> Self {
+        Self {
+            magic: REPLAY_SNAPSHOT_MAGIC,
+            version: REPLAY_SNAPSHOT_VERSION,
+            page_size: REPLAY_PAGE_SIZE as u32,
+            page_count,
+            memory_size,
+            memory_hash,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0_u8; Self::SIZE];
+        bytes[0..4].copy_from_slice(&self.magic.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.version.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.page_size.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.page_count.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.memory_size.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.memory_hash.to_le_bytes());
+        bytes
+    }
+
+    pub fn parse(prefix: &[u8]) -> Result<Self, ReplaySnapshotError> {
+        if prefix.len() < Self::SIZE || replay_read_u32(prefix, 0) != REPLAY_SNAPSHOT_MAGIC {
+            return Err(ReplaySnapshotError::NotASnapshot);
+        }
+
+        let header = Self {
+            magic: replay_read_u32(prefix, 0),
+            version: replay_read_u32(prefix, 4),
+            page_size: replay_read_u32(prefix, 8),
+            page_count: replay_read_u32(prefix, 12),
+            memory_size: replay_read_u64(prefix, 16),
+            memory_hash: replay_read_u64(prefix, 24),
+        };
+        if header.version != REPLAY_SNAPSHOT_VERSION {
+            return Err(ReplaySnapshotError::UnsupportedVersion(header.version));
+        }
+        if header.page_size as usize != REPLAY_PAGE_SIZE {
+            return Err(ReplaySnapshotError::PageSizeMismatch {
+                found: header.page_size,
+            });
+        }
+        Ok(header)
+    }
+
+    /// Where the first page's bytes start, after the page index list.
+    pub fn pages_offset(&self) -> u64 {
+        Self::SIZE as u64 + self.page_count as u64 * size_of::<u32>() as u64
+    }
+}
+
+/// What replay_hash_memory() gives for memory that is entirely zero, e.g. freshly allocated.
+pub const REPLAY_EMPTY_MEMORY_HASH: u64 = 0xCBF2_9CE4_8422_2325;
+
+pub fn replay_page_is_zero(page: &[u8]) -> bool {
+    // NOTE(aalhendi): OR-ing whole chunks vectorizes, an early-out on every byte doesn't.
+    page.chunks(64)
+        .all(|chunk| chunk.iter().fold(0, |bits, &byte| bits | byte) == 0)
+}
+
+/// Folds one non-zero page into a replay_hash_memory() hash. Pages must be hashed in order.
+pub fn replay_hash_page(hash: u64, page_index: u32, page: &[u8]) -> u64 {
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+    let mut hash = (hash ^ page_index as u64).wrapping_mul(FNV_PRIME);
+    let mut words = page.chunks_exact(8);
+    for word in &mut words {
+        let word = u64::from_le_bytes([
+            word[0], word[1], word[2], word[3], word[4], word[5], word[6], word[7],
+        ]);
+        hash = (hash ^ word).wrapping_mul(FNV_PRIME);
+    }
+    for &byte in words.remainder() {
+        hash = (hash ^ byte as u64).wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// FNV-1a over the index and 8-byte words of every non-zero `REPLAY_PAGE_SIZE` page, used to tie a
+/// recording to the memory snapshot it starts from. Zero pages are skipped so a sparse snapshot
+/// hashes the same as the memory it was taken from.
+pub fn replay_hash_memory(memory: &[u8]) -> u64 {
+    memory
+        .chunks(REPLAY_PAGE_SIZE)
+        .enumerate()
+        .filter(|(_, page)| !replay_page_is_zero(page))
+        .fold(REPLAY_EMPTY_MEMORY_HASH, |hash, (page_index, page)| {
+            replay_hash_page(hash, page_index as u32, page)
+        })
+}
+
+/// Loads a `loop_edit_N_state.hmi` snapshot into `memory`, which must be zeroed and exactly as large
+/// as the memory the snapshot was taken from. The pages are rehashed to catch a damaged file.
+pub fn replay_read_snapshot(
+    path: &std::path::Path,
+    memory: &mut [u8],
+) -> Result<ReplaySnapshotHeader, String> {
+    use std::io::Read;
+
+    let error = |e: String| format!("Failed to read snapshot {}: {e}", path.display());
+    let file = std::fs::File::open(path).map_err(|e| error(e.to_string()))?;
+    let mut file = std::io::BufReader::new(file);
+
+    let mut prefix = [0_u8; ReplaySnapshotHeader::SIZE];
+    file.read_exact(&mut prefix)
+        .map_err(|_| error("not a replay memory snapshot".to_string()))?;
+    let header = ReplaySnapshotHeader::parse(&prefix).map_err(|e| error(e.to_string()))?;
+    if header.memory_size != memory.len() as u64 {
+        return Err(error(format!(
+            "it holds {} bytes of memory but game memory is {}",
+            header.memory_size,
+            memory.len()
+        )));
+    }
+
+    let page_indices: Vec<u32> = (0..header.page_count)
+        .map(|_| {
+            let mut index = [0_u8; 4];
+            file.read_exact(&mut index)
+                .map(|()| u32::from_le_bytes(index))
+        })
+        .collect::<std::io::Result<_>>()
+        .map_err(|e| error(e.to_string()))?;
+
+    let mut memory_hash = REPLAY_EMPTY_MEMORY_HASH;
+    let mut pages = memory.chunks_mut(REPLAY_PAGE_SIZE);
+    let mut next_page_index = 0;
+    for page_index in page_indices {
+        if page_index < next_page_index {
+            return Err(error("page indices are out of order".to_string()));
+        }
+        let page = pages
+            .nth((page_index - next_page_index) as usize)
+            .ok_or_else(|| error(format!("page {page_index} is outside of memory")))?;
+        file.read_exact(page).map_err(|e| error(e.to_string()))?;
+        memory_hash = replay_hash_page(memory_hash, page_index, page);
+        next_page_index = page_index + 1;
+    }
+
+    if memory_hash != header.memory_hash {
+        return Err(error("its pages don't match its memory hash".to_string()));
+    }
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NOTE(aalhendi): 3x2 pixels with a pitch of 5, so each row has 2 pixels of padding.
+    fn padded_buffer(pixels: &mut [u32; 10]) -> GameOffscreenBuffer {
+        GameOffscreenBuffer {
+            memory: pixels.as_mut_ptr().cast::<core::ffi::c_void>(),
+            width: 3,
+            height: 2,
+            pitch: 5 * 4,
+            bytes_per_pixel: 4,
+        }
+    }
+
+    #[test]
+    fn buffer_rows_skip_the_pitch_padding() {
+        let mut pixels = [0xdead_u32; 10];
+        let mut buffer = padded_buffer(&mut pixels);
+        for (y, row) in buffer.rows_mut().enumerate() {
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = (y * 10 + x) as u32;
+            }
+        }
+        assert_eq!(buffer.row(0), [0, 1, 2]);
+        assert_eq!(buffer.row(1), [10, 11, 12]);
+        assert_eq!(buffer.rows().count(), 2);
+
+        buffer.set_pixel(2, 1, 99);
+        buffer.set_pixel(3, 0, 99);
+        buffer.set_pixel(-1, 0, 99);
+        buffer.set_pixel(0, 2, 99);
+        assert_eq!(buffer.pixel(2, 1), Some(99));
+        assert_eq!(buffer.pixel(3, 0), None);
+        assert_eq!(buffer.pixel(0, -1), None);
+
+        assert_eq!(
+            pixels,
+            [0, 1, 2, 0xdead, 0xdead, 10, 11, 99, 0xdead, 0xdead]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Buffer size can't be negative")]
+    fn buffer_rejects_negative_width() {
+        let mut pixels = [0_u32; 10];
+        let mut buffer = padded_buffer(&mut pixels);
+        buffer.width = -3;
+        buffer.row(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Rows must be aligned and can't overlap")]
+    fn buffer_rejects_pitch_shorter_than_a_row() {
+        let mut pixels = [0_u32; 10];
+        let mut buffer = padded_buffer(&mut pixels);
+        buffer.pitch = 2 * 4;
+        buffer.row(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Row 2 is outside the buffer")]
+    fn buffer_rejects_rows_past_the_end() {
+        let mut pixels = [0_u32; 10];
+        padded_buffer(&mut pixels).row(2);
+    }
+
+    #[test]
+    fn arena_nested_temporary_memory_rolls_back_each_scope() {
+        let mut memory = [0_u8; 64];
+        let mut arena = MemoryArena::new(memory.as_mut_ptr(), memory.len());
+        arena.push_size(8, 1);
+
+        let outer = arena.begin_temporary_memory();
+        arena.push_size(8, 1);
+        let inner = arena.begin_temporary_memory();
+        arena.push_size(16, 1);
+        assert_eq!((arena.used, arena.temp_count), (32, 2));
+
+        arena.end_temporary_memory(inner);
+        assert_eq!((arena.used, arena.temp_count), (16, 1));
+        arena.end_temporary_memory(outer);
+        assert_eq!((arena.used, arena.temp_count), (8, 0));
+        arena.check_arena();
+    }
+
+    #[test]
+    #[cfg_attr(
+        debug_assertions,
+        should_panic(expected = "Temporary memory scope 0 ended while scope 1 is still open")
+    )]
+    fn arena_temporary_memory_ends_innermost_first() {
+        let mut memory = [0_u8; 64];
+        let mut arena = MemoryArena::new(memory.as_mut_ptr(), memory.len());
+        let outer = arena.begin_temporary_memory();
+        let inner = arena.begin_temporary_memory();
+        arena.end_temporary_memory(outer);
+        // NOTE(aalhendi): release builds don't check, the arena just ends up at the outer scope.
+        arena.end_temporary_memory(inner);
+        assert_eq!((arena.used, arena.temp_count), (0, 0));
+    }
+
+    #[test]
+    fn arena_reset_throws_everything_away() {
+        let mut memory = [0_u8; 64];
+        let mut arena = MemoryArena::new(memory.as_mut_ptr(), memory.len());
+        let temp = arena.begin_temporary_memory();
+        arena.push_array::<u32>(4);
+        arena.end_temporary_memory(temp);
+        arena.push_size(40, 8);
+        arena.reset();
+        assert_eq!((arena.used, arena.remaining()), (0, 64));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "1 temporary memory scopes are still open")]
+    fn arena_reset_with_an_open_scope_panics() {
+        let mut memory = [0_u8; 64];
+        let mut arena = MemoryArena::new(memory.as_mut_ptr(), memory.len());
+        let _temp = arena.begin_temporary_memory();
+        arena.reset();
+    }
+
+    fn parse_error(text: &str) -> String {
+        match InputBindings::parse(text, "test.txt") {
+            Ok(_) => panic!("{text:?} parsed"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn default_bindings_parse() {
+        let bindings = InputBindings::default();
+        assert_eq!(
+            bindings.key(InputKey::Character(b'W')),
+            Some(GameButton::MoveUp)
+        );
+        assert_eq!(
+            bindings.key(InputKey::Character(b'D')),
+            Some(GameButton::MoveRight)
+        );
+        assert_eq!(bindings.key(InputKey::Up), Some(GameButton::ActionUp));
+        assert_eq!(bindings.key(InputKey::Escape), Some(GameButton::Start));
+        assert_eq!(bindings.key(InputKey::Space), Some(GameButton::Back));
+        assert_eq!(bindings.key(InputKey::Character(b'P')), None);
+        assert_eq!(bindings.key(InputKey::Character(b'1')), None);
+        assert_eq!(
+            bindings.gamepad_button(GamepadButton::A),
+            Some(GameButton::ActionDown)
+        );
+        assert_eq!(
+            bindings.gamepad_button(GamepadButton::Back),
+            Some(GameButton::Back)
+        );
+        for gamepad_button in GamepadButton::ALL {
+            assert!(bindings.gamepad_button(gamepad_button).is_some());
+        }
+    }
+
+    #[test]
+    fn bindings_skip_comments_and_blank_lines() {
+        let bindings = InputBindings::parse(
+            "# movement\n\n   \nkey J MoveLeft   # left hand\n  gamepad Y MoveUp\n",
+            "test.txt",
+        )
+        .unwrap();
+        assert_eq!(
+            bindings.key(InputKey::Character(b'J')),
+            Some(GameButton::MoveLeft)
+        );
+        assert_eq!(
+            bindings.gamepad_button(GamepadButton::Y),
+            Some(GameButton::MoveUp)
+        );
+        // NOTE(aalhendi): a file replaces the defaults instead of adding to them.
+        assert_eq!(bindings.key(InputKey::Character(b'W')), None);
+        assert_eq!(bindings.gamepad_button(GamepadButton::A), None);
+    }
+
+    #[test]
+    fn bindings_names() {
+        let bindings =
+            InputBindings::parse("key f Start\nkey F1 Back\nkey 7 ActionUp\n", "test.txt").unwrap();
+        assert_eq!(
+            bindings.key(InputKey::Character(b'F')),
+            Some(GameButton::Start)
+        );
+        assert_eq!(bindings.key(InputKey::Function(1)), Some(GameButton::Back));
+        assert_eq!(
+            bindings.key(InputKey::Character(b'7')),
+            Some(GameButton::ActionUp)
+        );
+        assert_eq!(InputKey::from_name("F12"), Some(InputKey::Function(12)));
+        assert_eq!(InputKey::from_name("F13"), None);
+        assert_eq!(InputKey::from_name("up"), None);
+    }
+
+    #[test]
+    fn bindings_allow_several_keys_per_button() {
+        let bindings =
+            InputBindings::parse("key W MoveUp\nkey Up MoveUp\nkey W MoveUp\n", "test.txt")
+                .unwrap();
+        assert_eq!(
+            bindings.key(InputKey::Character(b'W')),
+            Some(GameButton::MoveUp)
+        );
+        assert_eq!(bindings.key(InputKey::Up), Some(GameButton::MoveUp));
+    }
+
+    #[test]
+    fn bindings_reject_a_key_bound_twice() {
+        assert_eq!(
+            parse_error("key W MoveUp\nkey W MoveDown\n"),
+            "test.txt:2: W is already bound to MoveUp"
+        );
+        assert_eq!(
+            parse_error("gamepad A ActionDown\n# again\ngamepad A Start\n"),
+            "test.txt:3: A is already bound to ActionDown"
+        );
+    }
+
+    #[test]
+    fn bindings_reject_unknown_names() {
+        assert_eq!(parse_error("key Meta MoveUp"), "test.txt:1: unknown key");
+        assert_eq!(parse_error("key F13 MoveUp"), "test.txt:1: unknown key");
+        assert_eq!(
+            parse_error("gamepad Z MoveUp"),
+            "test.txt:1: unknown gamepad button"
+        );
+        assert_eq!(parse_error("key W Jump"), "test.txt:1: unknown GameButton");
+        assert_eq!(
+            parse_error("mouse W MoveUp"),
+            "test.txt:1: expected key or gamepad"
+        );
+        assert_eq!(parse_error("key W"), "test.txt:1: expected three fields");
+        assert_eq!(
+            parse_error("key W MoveUp now"),
+            "test.txt:1: expected three fields"
+        );
+    }
+
+    fn replay_stream_size(frame_count: u64) -> u64 {
+        ReplayHeader::SIZE as u64 + frame_count * size_of::<GameInput>() as u64
+    }
+
+    #[test]
+    fn replay_header_round_trips() {
+        let mut header = ReplayHeader::new(1.0 / 30.0, 0x1234);
+        header.frame_count = 3;
+        let parsed = ReplayHeader::parse(&header.to_bytes(), replay_stream_size(3)).unwrap();
+        assert_eq!(parsed, header);
+        assert_eq!(parsed.data_offset(), ReplayHeader::SIZE as u64);
+    }
+
+    #[test]
+    fn replay_header_trusts_the_stream_of_an_unfinished_recording() {
+        let header = ReplayHeader::new(1.0 / 30.0, 0);
+        let parsed = ReplayHeader::parse(&header.to_bytes(), replay_stream_size(5)).unwrap();
+        assert_eq!(parsed.frame_count, 5);
+    }
+
+    #[test]
+    fn replay_header_rejects_a_bad_magic() {
+        let mut bytes = ReplayHeader::new(1.0 / 30.0, 0).to_bytes();
+        bytes[0..4].copy_from_slice(b"HMIS");
+        assert_eq!(
+            ReplayHeader::parse(&bytes, replay_stream_size(1)),
+            Err(ReplayHeaderError::NotAReplay)
+        );
+    }
+
+    #[test]
+    fn replay_header_rejects_headerless_streams() {
+        // NOTE(aalhendi): what a recording from before the header looks like, raw GameInputs.
+        let game_input_size = size_of::<GameInput>();
+        let stream = [0_u8; ReplayHeader::SIZE];
+        assert_eq!(
+            ReplayHeader::parse(&stream, 4 * game_input_size as u64),
+            Err(ReplayHeaderError::NotAReplay)
+        );
+        assert_eq!(
+            ReplayHeader::parse(&[], 0),
+            Err(ReplayHeaderError::NotAReplay)
+        );
+        assert_eq!(
+            ReplayHeader::parse(&stream[..8], 8),
+            Err(ReplayHeaderError::NotAReplay)
+        );
+    }
+
+    #[test]
+    fn replay_header_rejects_other_versions() {
+        let mut header = ReplayHeader::new(1.0 / 30.0, 0);
+        header.version = REPLAY_VERSION + 1;
+        assert_eq!(
+            ReplayHeader::parse(&header.to_bytes(), replay_stream_size(0)),
+            Err(ReplayHeaderError::UnsupportedVersion(REPLAY_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn replay_header_rejects_another_game_input_size() {
+        let mut header = ReplayHeader::new(1.0 / 30.0, 0);
+        header.game_input_size += 8;
+        let found = header.game_input_size;
+        assert_eq!(
+            ReplayHeader::parse(&header.to_bytes(), replay_stream_size(0)),
+            Err(ReplayHeaderError::GameInputSizeMismatch { found })
+        );
+    }
+
+    #[test]
+    fn replay_header_rejects_a_truncated_stream() {
+        let mut header = ReplayHeader::new(1.0 / 30.0, 0);
+        header.frame_count = 4;
+        assert_eq!(
+            ReplayHeader::parse(&header.to_bytes(), replay_stream_size(3)),
+            Err(ReplayHeaderError::FrameCountMismatch {
+                header: 4,
+                stream: 3
+            })
+        );
+    }
+
+    #[test]
+    fn platform_options_reject_paused_with_frames() {
+        let args = ["--paused", "--frames", "10"].map(String::from);
+        assert!(PlatformOptions::parse(&args).is_err());
+        assert!(PlatformOptions::parse(&args[1..]).is_ok());
+    }
+
+    #[test]
+    fn frame_arena_goes_on_top_of_transient_storage() {
+        let args = ["--transient-storage", "1M", "--frame-arena", "64K"].map(String::from);
+        let options = PlatformOptions::parse(&args).unwrap();
+        assert_eq!(options.transient_storage_size, megabytes_to_bytes(1));
+        assert_eq!(options.frame_arena_size, 64 * 1024);
+
+        let mut block = [0_u8; 48];
+        let (transient_storage, frame_arena) = split_transient_block(block.as_mut_ptr(), 32, 16);
+        assert_eq!(transient_storage.cast::<u8>(), block.as_mut_ptr());
+        assert_eq!(frame_arena.base, block.as_mut_ptr().wrapping_add(32));
+        assert_eq!(frame_arena.size, 16);
+    }
 }
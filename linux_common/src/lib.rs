@@ -0,0 +1,931 @@
+//! The parts of the Linux platform that don't need a window or a sound device: loading the game
+//! library, the `PlatformApi` the game calls into, memory, clocks and writing sound to a WAV file.
+//! Both the windowed platform and the headless one are built on it.
+
+use core::{
+    cell::UnsafeCell,
+    ffi, mem, ptr,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use interface::{
+    GameGetApiHashFn, GameGetSoundSamplesFn, GameSoundOutputBuffer, GameUpdateAndRenderFn,
+    PLATFORM_API_VERSION, PlatformApi, PlatformFileGroup, PlatformFileHandle, PlatformFileSystem,
+    PlatformFileType, PlatformReadFileResult, PlatformWorkQueue, PlatformWorkQueueCallback,
+    ThreadContext, game_api_check, safe_truncate_i64_to_u32,
+};
+use std::{
+    ffi::{CString, OsStr},
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::{
+        fd::{FromRawFd, IntoRawFd, RawFd},
+        unix::{ffi::OsStrExt, fs::FileExt},
+    },
+    path::{Path, PathBuf},
+};
+
+#[cfg(not(target_os = "linux"))]
+compile_error!("linux_common can only be built on Linux.");
+
+pub const PATH_MAX_USIZE: usize = libc::PATH_MAX as usize;
+
+/// A loaded game library. It is dlclose'd (and its temp copy removed) on drop.
+pub struct LinuxGameCode {
+    // dlopen handle (we'll need this to unload it later)
+    game_code_lib: *mut ffi::c_void,
+    // The last time the shared object was modified
+    pub last_write_time: LinuxFileTime,
+    // The copy we actually dlopen'd, removed again on unload
+    temp_lib_name: [u8; PATH_MAX_USIZE],
+
+    // The function pointers
+    pub update_and_render: Option<GameUpdateAndRenderFn>,
+    pub get_sound_samples: Option<GameGetSoundSamplesFn>,
+
+    pub is_valid: bool,
+}
+
+impl Default for LinuxGameCode {
+    fn default() -> Self {
+        Self {
+            game_code_lib: ptr::null_mut(),
+            last_write_time: LinuxFileTime::default(),
+            temp_lib_name: [0; PATH_MAX_USIZE],
+            update_and_render: None,
+            get_sound_samples: None,
+            is_valid: false,
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub struct LinuxFileTime {
+    seconds: i64,
+    nanoseconds: i64,
+}
+
+/// Returns the zero time if the file can't be stat'd, e.g. while the linker is replacing it.
+#[inline]
+pub fn linux_get_last_write_time(file_name: &ffi::CStr) -> LinuxFileTime {
+    let mut data = unsafe { mem::zeroed::<libc::stat>() };
+    let result = unsafe { libc::stat(file_name.as_ptr(), &mut data) };
+    if result != 0 {
+        return LinuxFileTime::default();
+    }
+    LinuxFileTime {
+        seconds: data.st_mtime,
+        nanoseconds: data.st_mtime_nsec,
+    }
+}
+
+pub fn linux_path(file_name: &ffi::CStr) -> &OsStr {
+    OsStr::from_bytes(file_name.to_bytes())
+}
+
+/// Loads a copy of `source_lib_name` from `temp_lib_name`, so the original can be rebuilt while it runs.
+pub fn linux_load_game_code(
+    source_lib_name: &ffi::CStr,
+    temp_lib_name: &ffi::CStr,
+) -> LinuxGameCode {
+    // NOTE(aalhendi): we load a copy so the build can overwrite the original while we're running it.
+    let mut game_code = LinuxGameCode {
+        last_write_time: linux_get_last_write_time(source_lib_name),
+        ..LinuxGameCode::default()
+    };
+    if let Err(e) = std::fs::copy(linux_path(source_lib_name), linux_path(temp_lib_name)) {
+        eprintln!("Failed to copy game code to temp file: {e}");
+        game_code.last_write_time = LinuxFileTime::default();
+        return game_code;
+    }
+    let temp_bytes = temp_lib_name.to_bytes_with_nul();
+    game_code.temp_lib_name[..temp_bytes.len()].copy_from_slice(temp_bytes);
+
+    if let Err(e) = linux_open_game_code(&mut game_code, temp_lib_name) {
+        eprintln!("{e} ({})", source_lib_name.to_string_lossy());
+        if game_code.game_code_lib.is_null() {
+            // NOTE(aalhendi): most likely a half-written library, forget the time so we retry next frame.
+            game_code.last_write_time = LinuxFileTime::default();
+        }
+        // NOTE(aalhendi): otherwise the API hash didn't match. Keep last_write_time so we only
+        //  complain again once it is rebuilt.
+    }
+    game_code
+}
+
+/// Loads the game library at `lib_name` as it is, without the temp copy hot reloading needs.
+pub fn linux_load_game_code_in_place(lib_name: &ffi::CStr) -> Result<LinuxGameCode, String> {
+    let mut game_code = LinuxGameCode::default();
+    // NOTE(aalhendi): on failure game_code drops here, which closes a library that did open.
+    linux_open_game_code(&mut game_code, lib_name)?;
+    Ok(game_code)
+}
+
+/// dlopens `lib_name` into `game_code` and looks up its entry points once the API hash checks out.
+/// A library that opens but fails the check stays in `game_code_lib` so unloading closes it.
+fn linux_open_game_code(game_code: &mut LinuxGameCode, lib_name: &ffi::CStr) -> Result<(), String> {
+    unsafe {
+        let game_code_lib_handle =
+            libc::dlopen(lib_name.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+        if game_code_lib_handle.is_null() {
+            return Err(format!(
+                "Failed to load game code: {}",
+                ffi::CStr::from_ptr(libc::dlerror()).to_string_lossy()
+            ));
+        }
+        game_code.game_code_lib = game_code_lib_handle;
+
+        // NOTE(aalhendi): dlsym returns null for a missing symbol, which transmutes to None.
+        let api_hash_proc = libc::dlsym(game_code_lib_handle, c"game_get_api_hash".as_ptr());
+        let get_api_hash =
+            mem::transmute::<*mut ffi::c_void, Option<GameGetApiHashFn>>(api_hash_proc);
+        // NOTE(aalhendi): a stale library would read and write our structs with the wrong layout.
+        game_api_check(get_api_hash).map_err(|e| format!("Refusing to run game code: {e}"))?;
+
+        let update_proc = libc::dlsym(game_code_lib_handle, c"game_update_and_render".as_ptr());
+        let sound_proc = libc::dlsym(game_code_lib_handle, c"game_get_sound_samples".as_ptr());
+
+        game_code.update_and_render =
+            mem::transmute::<*mut ffi::c_void, Option<GameUpdateAndRenderFn>>(update_proc);
+        game_code.get_sound_samples =
+            mem::transmute::<*mut ffi::c_void, Option<GameGetSoundSamplesFn>>(sound_proc);
+        game_code.is_valid =
+            game_code.update_and_render.is_some() && game_code.get_sound_samples.is_some();
+    }
+    Ok(())
+}
+
+impl Drop for LinuxGameCode {
+    fn drop(&mut self) {
+        // NOTE(aalhendi): mostly so we don't leave temp copies lying around next to the exe.
+        linux_unload_game_code(self);
+    }
+}
+
+fn linux_unload_game_code(game_code: &mut LinuxGameCode) {
+    if !game_code.game_code_lib.is_null() {
+        unsafe {
+            if libc::dlclose(game_code.game_code_lib) != 0 {
+                eprintln!(
+                    "Failed to unload game code: {}",
+                    ffi::CStr::from_ptr(libc::dlerror()).to_string_lossy()
+                );
+            }
+        }
+        game_code.game_code_lib = ptr::null_mut();
+    }
+
+    // NOTE(aalhendi): unlinking is fine even if the mapping is still alive, the inode outlives the name.
+    if game_code.temp_lib_name[0] != 0 {
+        unsafe {
+            libc::unlink(game_code.temp_lib_name.as_ptr().cast::<ffi::c_char>());
+        }
+        game_code.temp_lib_name[0] = 0;
+    }
+
+    game_code.is_valid = false;
+    game_code.get_sound_samples = None;
+    game_code.update_and_render = None;
+}
+
+#[inline(always)]
+pub fn linux_get_wall_clock() -> libc::timespec {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // NOTE(aalhendi): CLOCK_MONOTONIC can't jump backwards when the wall time is adjusted.
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time);
+    }
+    time
+}
+
+#[inline(always)]
+pub fn linux_get_seconds_elapsed(start: libc::timespec, end: libc::timespec) -> f64 {
+    (end.tv_sec - start.tv_sec) as f64 + (end.tv_nsec - start.tv_nsec) as f64 / 1_000_000_000_f64
+}
+
+/// Maps `size` bytes of zeroed read/write memory at exactly `base_address`.
+/// Returns null if that range is already taken (or the kernel can't honour it) so the caller can fall back.
+#[cfg(feature = "internal_build")]
+pub fn linux_allocate_memory_at(base_address: usize, size: usize) -> *mut ffi::c_void {
+    let memory = unsafe {
+        libc::mmap(
+            base_address as *mut ffi::c_void,
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED_NOREPLACE,
+            -1,
+            0,
+        )
+    };
+    if memory == libc::MAP_FAILED {
+        return ptr::null_mut();
+    }
+
+    // NOTE(aalhendi): kernels older than 4.17 don't know MAP_FIXED_NOREPLACE and treat the address as a hint.
+    if memory as usize != base_address {
+        unsafe {
+            libc::munmap(memory, size);
+        }
+        return ptr::null_mut();
+    }
+
+    memory
+}
+
+/// Reserves and commits zeroed, read/write memory. Returns null on failure.
+pub fn linux_allocate_memory(size: usize) -> *mut ffi::c_void {
+    let memory = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    if memory == libc::MAP_FAILED {
+        ptr::null_mut()
+    } else {
+        memory
+    }
+}
+
+/// Streams 16-bit stereo samples into a WAV file. The sizes in the header are kept up to date on
+/// `finish`, and on drop for a file that wasn't finished.
+pub struct LinuxWavFileSink {
+    file: File,
+    data_size: u32,
+}
+
+impl LinuxWavFileSink {
+    const HEADER_SIZE: u32 = 44;
+
+    pub fn create(
+        path: &OsStr,
+        samples_per_second: u32,
+        bytes_per_sample: u32,
+    ) -> io::Result<Self> {
+        let mut file = File::create(path)?;
+
+        // NOTE(aalhendi): the sizes are placeholders until we know how much we wrote, see patch_header.
+        let mut header = [0_u8; Self::HEADER_SIZE as usize];
+        header[0..4].copy_from_slice(b"RIFF");
+        header[8..12].copy_from_slice(b"WAVE");
+        header[12..16].copy_from_slice(b"fmt ");
+        header[16..20].copy_from_slice(&16_u32.to_le_bytes());
+        header[20..22].copy_from_slice(&1_u16.to_le_bytes()); // PCM
+        header[22..24].copy_from_slice(&2_u16.to_le_bytes()); // channels
+        header[24..28].copy_from_slice(&samples_per_second.to_le_bytes());
+        header[28..32].copy_from_slice(&(samples_per_second * bytes_per_sample).to_le_bytes());
+        header[32..34].copy_from_slice(&(bytes_per_sample as u16).to_le_bytes());
+        header[34..36].copy_from_slice(&16_u16.to_le_bytes()); // bits per channel
+        header[36..40].copy_from_slice(b"data");
+        file.write_all(&header)?;
+
+        let mut sink = Self { file, data_size: 0 };
+        sink.patch_header()?;
+        Ok(sink)
+    }
+
+    pub fn write_samples(&mut self, source_buffer: &GameSoundOutputBuffer) -> io::Result<()> {
+        // NOTE(aalhendi): WAV is little endian, and so are the platforms we build for.
+        let byte_count = source_buffer.sample_count as usize * 2 * size_of::<i16>();
+        let bytes =
+            unsafe { core::slice::from_raw_parts(source_buffer.samples.cast::<u8>(), byte_count) };
+        self.file.write_all(bytes)?;
+        self.data_size += byte_count as u32;
+        Ok(())
+    }
+
+    /// Patches the header now, so the caller hears about a failure instead of it being logged on drop.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.patch_header()?;
+        self.file.flush()
+    }
+
+    fn patch_header(&mut self) -> io::Result<()> {
+        let riff_size = Self::HEADER_SIZE - 8 + self.data_size;
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file.write_all(&riff_size.to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&self.data_size.to_le_bytes())?;
+        self.file.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+}
+
+impl Drop for LinuxWavFileSink {
+    fn drop(&mut self) {
+        if let Err(e) = self.patch_header() {
+            eprintln!("Failed to finish WAV file: {e}");
+        }
+    }
+}
+
+/// The services the game reaches through `GameMemory::platform`. The headless platform uses the same table.
+pub fn linux_platform_api(data_directory: PathBuf) -> PlatformApi {
+    PlatformApi {
+        version: PLATFORM_API_VERSION,
+        log: linux_platform_log,
+        get_wall_clock: linux_platform_get_wall_clock,
+        get_seconds_elapsed: linux_platform_get_seconds_elapsed,
+        read_cycle_counter: linux_platform_read_cycle_counter,
+        read_entire_file: linux_platform_read_entire_file,
+        write_entire_file: linux_platform_write_entire_file,
+        free_file_memory: linux_platform_free_file_memory,
+        file_system: linux_make_file_system(data_directory).cast::<PlatformFileSystem>(),
+        get_all_files_of_type_begin: linux_platform_get_all_files_of_type_begin,
+        get_all_files_of_type_end: linux_platform_get_all_files_of_type_end,
+        open_next_file: linux_platform_open_next_file,
+        open_file: linux_platform_open_file,
+        read_data_from_file: linux_platform_read_data_from_file,
+        file_error: linux_platform_file_error,
+        close_file: linux_platform_close_file,
+        allocate_memory: linux_platform_allocate_memory,
+        deallocate_memory: linux_platform_deallocate_memory,
+        work_queue: linux_make_work_queue().cast::<PlatformWorkQueue>(),
+        add_work_entry: linux_platform_add_work_entry,
+        complete_all_work: linux_platform_complete_all_work,
+    }
+}
+
+/// # Safety
+/// `message` must point to `message_size` readable bytes.
+unsafe extern "C" fn linux_platform_log(message: *const u8, message_size: usize) {
+    let message = unsafe { core::slice::from_raw_parts(message, message_size) };
+    println!("{}", String::from_utf8_lossy(message));
+}
+
+/// Nanoseconds on CLOCK_MONOTONIC.
+extern "C" fn linux_platform_get_wall_clock() -> u64 {
+    let time = linux_get_wall_clock();
+    time.tv_sec as u64 * 1_000_000_000 + time.tv_nsec as u64
+}
+
+extern "C" fn linux_platform_get_seconds_elapsed(start: u64, end: u64) -> f32 {
+    (end.wrapping_sub(start) as f64 / 1_000_000_000.0) as f32
+}
+
+extern "C" fn linux_platform_read_cycle_counter() -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    {
+        unsafe { core::arch::x86_64::_rdtsc() }
+    }
+    // TODO(aalhendi): a real cycle counter off x86_64. Nanoseconds will do for relative timings.
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        linux_platform_get_wall_clock()
+    }
+}
+
+extern "C" fn linux_platform_allocate_memory(size: usize) -> *mut ffi::c_void {
+    linux_allocate_memory(size)
+}
+
+/// # Safety
+/// `memory` must be null or come from linux_platform_allocate_memory with the same `size`, and not be used again.
+unsafe extern "C" fn linux_platform_deallocate_memory(memory: *mut ffi::c_void, size: usize) {
+    if !memory.is_null() && unsafe { libc::munmap(memory, size) } != 0 {
+        eprintln!("Failed to unmap memory: {}", io::Error::last_os_error());
+    }
+}
+
+const WORK_QUEUE_ENTRY_COUNT: u32 = 256;
+// NOTE(aalhendi): the main thread works too while it waits in complete_all_work.
+const MAX_WORKER_THREAD_COUNT: usize = 15;
+
+#[derive(Clone, Copy)]
+struct LinuxWorkQueueEntry {
+    // NOTE(aalhendi): Option so that zeroed memory is a valid entry.
+    callback: Option<PlatformWorkQueueCallback>,
+    data: *mut ffi::c_void,
+}
+
+/// A ring of entries with one writer, the main thread, and any number of readers. Zeroed memory is an
+/// empty queue, apart from the semaphore.
+struct LinuxWorkQueue {
+    completion_goal: AtomicU32,
+    completion_count: AtomicU32,
+    next_entry_to_write: AtomicU32,
+    next_entry_to_read: AtomicU32,
+    next_thread_index: AtomicU32,
+    // NOTE(aalhendi): counts the entries no worker has woken up for yet.
+    semaphore: UnsafeCell<libc::sem_t>,
+    entries: [UnsafeCell<LinuxWorkQueueEntry>; WORK_QUEUE_ENTRY_COUNT as usize],
+}
+
+fn linux_make_work_queue() -> *mut LinuxWorkQueue {
+    let queue = linux_allocate_memory(size_of::<LinuxWorkQueue>()).cast::<LinuxWorkQueue>();
+    assert!(!queue.is_null(), "Failed to allocate the work queue");
+    let queue_ref = unsafe { &*queue };
+    queue_ref.next_thread_index.store(1, Ordering::Relaxed);
+    if unsafe { libc::sem_init(queue_ref.semaphore.get(), 0, 0) } != 0 {
+        panic!(
+            "Failed to create the work queue semaphore: {}",
+            io::Error::last_os_error()
+        );
+    }
+
+    let worker_thread_count = std::thread::available_parallelism()
+        .map_or(1, |count| count.get())
+        .saturating_sub(1)
+        .min(MAX_WORKER_THREAD_COUNT);
+    for _ in 0..worker_thread_count {
+        let mut thread = unsafe { mem::zeroed::<libc::pthread_t>() };
+        let result = unsafe {
+            libc::pthread_create(
+                &mut thread,
+                ptr::null(),
+                linux_work_queue_thread_proc,
+                queue.cast::<ffi::c_void>(),
+            )
+        };
+        if result != 0 {
+            // NOTE(aalhendi): not fatal, complete_all_work gets through the queue on its own.
+            eprintln!(
+                "Failed to start a worker thread: {}",
+                io::Error::from_raw_os_error(result)
+            );
+            break;
+        }
+        unsafe {
+            libc::pthread_detach(thread);
+        }
+    }
+
+    queue
+}
+
+extern "C" fn linux_work_queue_thread_proc(queue: *mut ffi::c_void) -> *mut ffi::c_void {
+    let queue = unsafe { &*queue.cast::<LinuxWorkQueue>() };
+    let mut thread = ThreadContext {
+        thread_index: queue.next_thread_index.fetch_add(1, Ordering::Relaxed),
+    };
+    loop {
+        if linux_do_next_work_queue_entry(queue, &mut thread) {
+            // NOTE(aalhendi): EINTR just means another look at the queue.
+            unsafe {
+                libc::sem_wait(queue.semaphore.get());
+            }
+        }
+    }
+}
+
+/// Runs the next entry if there is one. Returns true when the queue was empty, i.e. the caller can sleep.
+fn linux_do_next_work_queue_entry(queue: &LinuxWorkQueue, thread: &mut ThreadContext) -> bool {
+    let original_next_entry_to_read = queue.next_entry_to_read.load(Ordering::Acquire);
+    if original_next_entry_to_read == queue.next_entry_to_write.load(Ordering::Acquire) {
+        return true;
+    }
+
+    let new_next_entry_to_read = (original_next_entry_to_read + 1) % WORK_QUEUE_ENTRY_COUNT;
+    if queue
+        .next_entry_to_read
+        .compare_exchange(
+            original_next_entry_to_read,
+            new_next_entry_to_read,
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+        )
+        .is_ok()
+    {
+        // NOTE(aalhendi): winning the exchange makes the entry ours. The writer can't reuse it until
+        //  next_entry_to_read comes all the way around, which a full queue assert stops first.
+        let entry = unsafe { *queue.entries[original_next_entry_to_read as usize].get() };
+        if let Some(callback) = entry.callback {
+            unsafe { callback(thread, entry.data) };
+        }
+        queue.completion_count.fetch_add(1, Ordering::Release);
+    }
+    false
+}
+
+/// # Safety
+/// `queue` must be the work_queue from linux_platform_api, and this must be called on the main thread.
+unsafe extern "C" fn linux_platform_add_work_entry(
+    queue: *mut PlatformWorkQueue,
+    callback: PlatformWorkQueueCallback,
+    data: *mut ffi::c_void,
+) {
+    let queue = unsafe { &*queue.cast::<LinuxWorkQueue>() };
+    let next_entry_to_write = queue.next_entry_to_write.load(Ordering::Relaxed);
+    let new_next_entry_to_write = (next_entry_to_write + 1) % WORK_QUEUE_ENTRY_COUNT;
+    assert!(
+        new_next_entry_to_write != queue.next_entry_to_read.load(Ordering::Acquire),
+        "Work queue is full"
+    );
+    unsafe {
+        *queue.entries[next_entry_to_write as usize].get() = LinuxWorkQueueEntry {
+            callback: Some(callback),
+            data,
+        };
+    }
+    queue.completion_goal.fetch_add(1, Ordering::Relaxed);
+    queue
+        .next_entry_to_write
+        .store(new_next_entry_to_write, Ordering::Release);
+    unsafe {
+        libc::sem_post(queue.semaphore.get());
+    }
+}
+
+/// # Safety
+/// `queue` must be the work_queue from linux_platform_api, and this must be called on the main thread.
+unsafe extern "C" fn linux_platform_complete_all_work(
+    thread: &mut ThreadContext,
+    queue: *mut PlatformWorkQueue,
+) {
+    let queue = unsafe { &*queue.cast::<LinuxWorkQueue>() };
+    while queue.completion_count.load(Ordering::Acquire)
+        != queue.completion_goal.load(Ordering::Relaxed)
+    {
+        linux_do_next_work_queue_entry(queue, thread);
+    }
+    queue.completion_goal.store(0, Ordering::Relaxed);
+    queue.completion_count.store(0, Ordering::Relaxed);
+}
+
+/// # Safety
+/// `memory` must be null or a pointer handed out by linux_platform_read_entire_file that hasn't been freed yet.
+unsafe extern "C" fn linux_platform_free_file_memory(
+    _thread: &mut ThreadContext,
+    memory: *mut ffi::c_void,
+) {
+    if !memory.is_null() {
+        unsafe {
+            libc::free(memory);
+        }
+    } else {
+        eprintln!("Failed to free file memory: ptr is null");
+    }
+}
+
+/// Writes the file next to its destination and renames it into place, so readers never see half a file.
+///
+/// # Safety
+/// `filename` must be a valid C string and `memory` must point to at least `memory_size` readable bytes.
+unsafe extern "C" fn linux_platform_write_entire_file(
+    _thread: &mut ThreadContext,
+    filename: *const ffi::c_char,
+    memory_size: u32,
+    memory: *mut ffi::c_void,
+) -> bool {
+    let filename = unsafe { ffi::CStr::from_ptr(filename) };
+    let contents =
+        unsafe { core::slice::from_raw_parts(memory.cast::<u8>(), memory_size as usize) };
+
+    let mut temp_file_name = linux_path(filename).to_os_string();
+    temp_file_name.push(".tmp");
+
+    let write_result = (|| -> io::Result<()> {
+        let mut file = File::create(&temp_file_name)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&temp_file_name, linux_path(filename))
+    })();
+
+    match write_result {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("Failed to write file {filename:?}: {e}");
+            let _ = std::fs::remove_file(&temp_file_name);
+            false
+        }
+    }
+}
+
+/// # Safety
+/// `filename` must be a valid C string. The returned memory must go back through linux_platform_free_file_memory.
+unsafe extern "C" fn linux_platform_read_entire_file(
+    thread: &mut ThreadContext,
+    filename: *const ffi::c_char,
+) -> PlatformReadFileResult {
+    let filename = unsafe { ffi::CStr::from_ptr(filename) };
+    let failed = PlatformReadFileResult {
+        memory: ptr::null_mut(),
+        size: 0,
+    };
+
+    let mut file = match File::open(linux_path(filename)) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Failed to open file {filename:?}: {e}");
+            return failed;
+        }
+    };
+    let file_size = match file.metadata() {
+        Ok(metadata) => metadata.len() as i64,
+        Err(e) => {
+            eprintln!("Failed to get size of file {filename:?}: {e}");
+            return failed;
+        }
+    };
+    let file_size_u32 = safe_truncate_i64_to_u32(file_size);
+
+    if file_size == 0 {
+        // NOTE(aalhendi): Reading an empty file isn't an error. We return null and let the caller handle it.
+        return failed;
+    }
+
+    // NOTE(aalhendi): malloc rather than mmap because free_file_memory only gets the pointer back, not the size.
+    let memory_ptr = unsafe { libc::malloc(file_size_u32 as usize) };
+    if memory_ptr.is_null() {
+        // NOTE(aalhendi): no panicking in here, it would unwind into the game's extern "C" call.
+        eprintln!("Failed to allocate {file_size_u32} bytes for file {filename:?}");
+        return failed;
+    }
+
+    let contents =
+        unsafe { core::slice::from_raw_parts_mut(memory_ptr.cast::<u8>(), file_size_u32 as usize) };
+    if let Err(e) = file.read_exact(contents) {
+        unsafe {
+            linux_platform_free_file_memory(thread, memory_ptr);
+        }
+        eprintln!("Failed to read file {filename:?}: {e}");
+        return failed;
+    }
+
+    PlatformReadFileResult {
+        memory: memory_ptr,
+        size: file_size_u32,
+    }
+}
+
+const LINUX_CLOSED_FILE: usize = usize::MAX;
+
+struct LinuxFileSystem {
+    data_directory: PathBuf,
+}
+
+struct LinuxFileGroup {
+    dir: *mut libc::DIR,
+    file_type: PlatformFileType,
+    file_system: *const LinuxFileSystem,
+}
+
+// NOTE(aalhendi): like the work queue, it lives as long as the PlatformApi that points at it, which
+//  is the rest of the process.
+fn linux_make_file_system(data_directory: PathBuf) -> *mut LinuxFileSystem {
+    let file_system = linux_allocate_memory(size_of::<LinuxFileSystem>()).cast::<LinuxFileSystem>();
+    assert!(!file_system.is_null(), "Failed to allocate the file system");
+    unsafe {
+        file_system.write(LinuxFileSystem { data_directory });
+    }
+    file_system
+}
+
+fn linux_file_error(handle: &mut PlatformFileHandle, message: &str) {
+    eprintln!("File error: {message}");
+    handle.has_errors = true;
+}
+
+fn linux_open_file_handle(path: &Path) -> PlatformFileHandle {
+    let mut handle = PlatformFileHandle {
+        has_errors: false,
+        platform: LINUX_CLOSED_FILE,
+    };
+    match File::open(path) {
+        Ok(file) => handle.platform = file.into_raw_fd() as usize,
+        Err(e) => linux_file_error(
+            &mut handle,
+            &format!("Failed to open {}: {e}", path.display()),
+        ),
+    }
+    handle
+}
+
+/// # Safety
+/// `dir` must be open.
+unsafe fn linux_read_next_file_of_type(
+    dir: *mut libc::DIR,
+    file_type: PlatformFileType,
+) -> Option<CString> {
+    loop {
+        let entry = unsafe { libc::readdir(dir) };
+        if entry.is_null() {
+            return None;
+        }
+        let name = unsafe { ffi::CStr::from_ptr((*entry).d_name.as_ptr()) };
+        let extension = Path::new(OsStr::from_bytes(name.to_bytes())).extension();
+        if unsafe { (*entry).d_type } != libc::DT_DIR
+            && extension == Some(OsStr::new(file_type.extension()))
+        {
+            return Some(name.to_owned());
+        }
+    }
+}
+
+/// # Safety
+/// `file_system` must come from linux_platform_api.
+unsafe extern "C" fn linux_platform_get_all_files_of_type_begin(
+    file_system: *mut PlatformFileSystem,
+    file_type: PlatformFileType,
+) -> PlatformFileGroup {
+    let empty = PlatformFileGroup {
+        file_count: 0,
+        platform: ptr::null_mut(),
+    };
+    let file_system = file_system.cast::<LinuxFileSystem>();
+    let data_directory = unsafe { &(*file_system).data_directory };
+    let Ok(data_directory_c) = CString::new(data_directory.as_os_str().as_bytes()) else {
+        return empty;
+    };
+    let dir = unsafe { libc::opendir(data_directory_c.as_ptr()) };
+    if dir.is_null() {
+        let e = io::Error::last_os_error();
+        if e.kind() != io::ErrorKind::NotFound {
+            eprintln!(
+                "Failed to open the data directory {}: {e}",
+                data_directory.display()
+            );
+        }
+        return empty;
+    }
+
+    let mut file_count = 0;
+    while unsafe { linux_read_next_file_of_type(dir, file_type) }.is_some() {
+        file_count += 1;
+    }
+    unsafe {
+        libc::rewinddir(dir);
+    }
+
+    let group = linux_allocate_memory(size_of::<LinuxFileGroup>()).cast::<LinuxFileGroup>();
+    if group.is_null() {
+        unsafe {
+            libc::closedir(dir);
+        }
+        return empty;
+    }
+    unsafe {
+        group.write(LinuxFileGroup {
+            dir,
+            file_type,
+            file_system,
+        });
+    }
+    PlatformFileGroup {
+        file_count,
+        platform: group.cast::<ffi::c_void>(),
+    }
+}
+
+/// # Safety
+/// `group` must come from linux_platform_get_all_files_of_type_begin.
+unsafe extern "C" fn linux_platform_get_all_files_of_type_end(group: &mut PlatformFileGroup) {
+    let linux_group = group.platform.cast::<LinuxFileGroup>();
+    if !linux_group.is_null() {
+        unsafe {
+            libc::closedir((*linux_group).dir);
+            linux_platform_deallocate_memory(linux_group.cast(), size_of::<LinuxFileGroup>());
+        }
+    }
+    group.file_count = 0;
+    group.platform = ptr::null_mut();
+}
+
+/// # Safety
+/// `group` must come from linux_platform_get_all_files_of_type_begin.
+unsafe extern "C" fn linux_platform_open_next_file(
+    group: &mut PlatformFileGroup,
+) -> PlatformFileHandle {
+    let linux_group = group.platform.cast::<LinuxFileGroup>();
+    let file_name = if linux_group.is_null() {
+        None
+    } else {
+        unsafe { linux_read_next_file_of_type((*linux_group).dir, (*linux_group).file_type) }
+    };
+
+    match file_name {
+        Some(file_name) => {
+            let data_directory = unsafe { &(*(*linux_group).file_system).data_directory };
+            linux_open_file_handle(&data_directory.join(OsStr::from_bytes(file_name.to_bytes())))
+        }
+        None => {
+            let mut handle = PlatformFileHandle {
+                has_errors: false,
+                platform: LINUX_CLOSED_FILE,
+            };
+            linux_file_error(&mut handle, "There are no more files in the group");
+            handle
+        }
+    }
+}
+
+/// # Safety
+/// `file_system` must come from linux_platform_api and `filename` must be a valid C string.
+unsafe extern "C" fn linux_platform_open_file(
+    file_system: *mut PlatformFileSystem,
+    filename: *const ffi::c_char,
+) -> PlatformFileHandle {
+    let data_directory = unsafe { &(*file_system.cast::<LinuxFileSystem>()).data_directory };
+    let filename = unsafe { ffi::CStr::from_ptr(filename) };
+    linux_open_file_handle(&data_directory.join(linux_path(filename)))
+}
+
+/// # Safety
+/// `handle` must come from one of the open functions and `dest` must point to `size` writable bytes.
+unsafe extern "C" fn linux_platform_read_data_from_file(
+    handle: &mut PlatformFileHandle,
+    offset: u64,
+    size: u64,
+    dest: *mut ffi::c_void,
+) {
+    let dest = unsafe { core::slice::from_raw_parts_mut(dest.cast::<u8>(), size as usize) };
+    if !handle.has_errors && handle.platform == LINUX_CLOSED_FILE {
+        linux_file_error(handle, "Read from a file that isn't open");
+    }
+    if !handle.has_errors {
+        // NOTE(aalhendi): pread, so reads of the same file from different threads don't fight over
+        //  the file position. ManuallyDrop because the handle still owns the fd.
+        let file = mem::ManuallyDrop::new(unsafe { File::from_raw_fd(handle.platform as RawFd) });
+        if let Err(e) = file.read_exact_at(dest, offset) {
+            linux_file_error(
+                handle,
+                &format!("Failed to read {size} bytes at offset {offset}: {e}"),
+            );
+        }
+    }
+    if handle.has_errors {
+        dest.fill(0);
+    }
+}
+
+/// # Safety
+/// `message` must point to `message_size` readable bytes.
+unsafe extern "C" fn linux_platform_file_error(
+    handle: &mut PlatformFileHandle,
+    message: *const u8,
+    message_size: usize,
+) {
+    let message = unsafe { core::slice::from_raw_parts(message, message_size) };
+    linux_file_error(handle, &String::from_utf8_lossy(message));
+}
+
+/// # Safety
+/// `handle` must come from one of the open functions.
+unsafe extern "C" fn linux_platform_close_file(handle: &mut PlatformFileHandle) {
+    if handle.platform != LINUX_CLOSED_FILE {
+        drop(unsafe { File::from_raw_fd(handle.platform as RawFd) });
+        handle.platform = LINUX_CLOSED_FILE;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reading_a_closed_file_is_a_file_error() {
+        let mut handle = PlatformFileHandle {
+            has_errors: false,
+            platform: LINUX_CLOSED_FILE,
+        };
+        let mut dest = [0xff_u8; 4];
+        unsafe {
+            linux_platform_read_data_from_file(
+                &mut handle,
+                0,
+                dest.len() as u64,
+                dest.as_mut_ptr().cast(),
+            );
+        }
+        assert!(handle.has_errors);
+        assert_eq!(dest, [0; 4]);
+    }
+
+    #[test]
+    fn each_platform_api_keeps_its_own_data_directory() {
+        let root = std::env::temp_dir().join(format!("hm_file_system_{}", std::process::id()));
+        let first_directory = root.join("first");
+        let second_directory = root.join("second");
+        std::fs::create_dir_all(&first_directory).unwrap();
+        std::fs::create_dir_all(&second_directory).unwrap();
+        std::fs::write(first_directory.join("a.hma"), b"a").unwrap();
+        std::fs::write(second_directory.join("b.hma"), b"b").unwrap();
+        std::fs::write(second_directory.join("c.hma"), b"c").unwrap();
+
+        let first = linux_platform_api(first_directory);
+        let second = linux_platform_api(second_directory);
+        for (platform, file_count, filename) in [(&first, 1, c"a.hma"), (&second, 2, c"b.hma")] {
+            let mut group = unsafe {
+                (platform.get_all_files_of_type_begin)(
+                    platform.file_system,
+                    PlatformFileType::Asset,
+                )
+            };
+            assert_eq!(group.file_count, file_count);
+            unsafe { (platform.get_all_files_of_type_end)(&mut group) };
+
+            let mut handle =
+                unsafe { (platform.open_file)(platform.file_system, filename.as_ptr()) };
+            assert!(!handle.has_errors);
+            unsafe { (platform.close_file)(&mut handle) };
+        }
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
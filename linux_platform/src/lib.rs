@@ -1,36 +1,1575 @@
-use core::num::NonZeroU32;
+use core::{ffi, mem, num::NonZeroU32, ptr};
+use interface::{
+    GameButton, GameButtonState, GameControllerInput, GameInput, GameMemory, GameOffscreenBuffer,
+    GameSoundOutputBuffer, GamepadButton, InputBindings, InputKey, PlatformOptions,
+    REPLAY_PAGE_SIZE, ReplayHeader, ThreadContext, split_transient_block,
+};
+use linux_common::{
+    LinuxFileTime, LinuxGameCode, LinuxWavFileSink, PATH_MAX_USIZE, linux_allocate_memory,
+    linux_get_last_write_time, linux_get_seconds_elapsed, linux_get_wall_clock,
+    linux_load_game_code, linux_platform_api,
+};
 use softbuffer::{Context, Surface};
-use std::rc::Rc;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::{
+        fd::AsRawFd,
+        unix::{ffi::OsStrExt, fs::OpenOptionsExt},
+    },
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 use winit::{
     application::ApplicationHandler,
-    event::WindowEvent,
+    dpi::PhysicalSize,
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{Key, KeyCode, ModifiersState, PhysicalKey},
+    platform::modifier_supplement::KeyEventExtModifierSupplement,
     window::{Window, WindowAttributes, WindowId},
 };
 
+#[cfg(feature = "internal_build")]
+use interface::{
+    REPLAY_EMPTY_MEMORY_HASH, ReplaySnapshotHeader, replay_hash_page, replay_page_is_zero,
+    replay_read_snapshot,
+};
+#[cfg(feature = "internal_build")]
+use linux_common::{linux_allocate_memory_at, linux_path};
+#[cfg(feature = "internal_build")]
+use std::ffi::OsStr;
+
 #[cfg(not(target_os = "linux"))]
 compile_error!("linux_platform can only be built on Linux.");
 
+// NOTE(aalhendi): winit doesn't say how many pixels a touchpad scroll is worth. This is a guess that feels
+//  about right next to a notched wheel.
+const PIXELS_PER_WHEEL_NOTCH: f64 = 50.0;
+// NOTE(aalhendi): controllers[0] is the keyboard; gamepads are stored after it.
+const MAX_GAMEPAD_COUNT: usize = 4;
+// NOTE(aalhendi): same as XINPUT_GAMEPAD_LEFT_THUMB_DEADZONE, as a fraction of a full stick push.
+const GAMEPAD_LEFT_THUMB_DEADZONE: f32 = 7849.0 / 32767.0;
+// NOTE(aalhendi): same as XINPUT_GAMEPAD_RIGHT_THUMB_DEADZONE.
+const GAMEPAD_RIGHT_THUMB_DEADZONE: f32 = 8689.0 / 32767.0;
+const GAMEPAD_RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+
 struct LinuxApp {
-    state: LinuxAppState,
+    window: LinuxAppWindow,
+    state: LinuxState,
+    backbuffer: LinuxOffscreenBuffer,
+    sound_output: LinuxSoundOutput,
+    gamepads: [LinuxGamepad; MAX_GAMEPAD_COUNT],
+    last_gamepad_scan: Option<Instant>,
+    game: LinuxGameCode,
+    // NOTE(aalhendi): bumped on every load so each temp copy gets a fresh name. glibc hands back the
+    //  already-loaded object when dlopen sees a path it knows, which would defeat the reload.
+    game_load_count: u32,
+    source_lib_name: [u8; PATH_MAX_USIZE],
+    game_memory: GameMemory,
+    new_input: GameInput,
+    old_input: GameInput,
+    modifiers: ModifiersState,
+    mouse_wheel_remainder: f64,
+    is_paused: bool,
+    // NOTE(aalhendi): counts down to 0 and exits when --frames is given.
+    frames_left: Option<u32>,
+    window_size: (i32, i32),
+    bindings: InputBindings,
+    game_update_hz: f32,
+    target_seconds_per_frame: f64,
+    last_counter: libc::timespec,
 }
 
-enum LinuxAppState {
+enum LinuxAppWindow {
     Uninitialized,
-    Running(LinuxState),
+    Running(LinuxWindow),
 }
-struct LinuxState {
+
+struct LinuxWindow {
     window: Rc<Window>,
     _context: Context<Rc<Window>>,
     surface: Surface<Rc<Window>, Rc<Window>>,
 }
 
+struct LinuxState {
+    total_size: usize,
+    game_memory_block: *mut (),
+    replay_buffers: [LinuxReplayBuffer; 4],
+
+    // NOTE(aalhendi): /proc/self/pagemap and room for one entry per OS page of game memory, to find
+    //  the pages the game has touched without faulting in the rest.
+    page_map: Option<File>,
+    page_map_entries: *mut u64,
+
+    recording_file: Option<File>,
+    // NOTE(aalhendi): written as a placeholder when recording starts, rewritten with the final frame
+    //  count and dt when it ends.
+    recording_header: ReplayHeader,
+    input_playing_idx: Option<u32>,
+
+    input_recording_idx: Option<u32>,
+    playback_file: Option<File>,
+    playback_data_offset: u64,
+    // NOTE(aalhendi): the slot L records into, the last one picked with the number keys.
+    #[cfg(feature = "internal_build")]
+    selected_replay_idx: u32,
+
+    pub exe_file_name: [u8; PATH_MAX_USIZE],
+    pub exe_file_name_base_offset: usize,
+}
+
+impl Default for LinuxState {
+    fn default() -> Self {
+        Self {
+            total_size: 0,
+            game_memory_block: ptr::null_mut(),
+            replay_buffers: Default::default(),
+            page_map: None,
+            page_map_entries: ptr::null_mut(),
+            recording_file: None,
+            recording_header: ReplayHeader::new(0.0, 0),
+            input_playing_idx: None,
+            input_recording_idx: None,
+            playback_file: None,
+            playback_data_offset: 0,
+            #[cfg(feature = "internal_build")]
+            selected_replay_idx: 0,
+            exe_file_name: [0; PATH_MAX_USIZE],
+            exe_file_name_base_offset: 0,
+        }
+    }
+}
+
+struct LinuxReplayBuffer {
+    pub file_name: [u8; PATH_MAX_USIZE],
+    // NOTE(aalhendi): laid out like game memory, but only the snapshot's pages are ever written so
+    //  the rest never gets backed by real memory.
+    pub memory_block: *mut (),
+    // NOTE(aalhendi): the pages held in memory_block, in order. Every other page of the snapshot is zero.
+    pub page_indices: *mut u32,
+    pub page_count: u32,
+    #[cfg(feature = "internal_build")]
+    pub memory_hash: u64,
+}
+
+impl Default for LinuxReplayBuffer {
+    fn default() -> Self {
+        Self {
+            file_name: [0; PATH_MAX_USIZE],
+            memory_block: ptr::null_mut(),
+            page_indices: ptr::null_mut(),
+            page_count: 0,
+            #[cfg(feature = "internal_build")]
+            memory_hash: REPLAY_EMPTY_MEMORY_HASH,
+        }
+    }
+}
+
+impl LinuxState {
+    fn linux_get_input_file_location(&self, input_stream: bool, slot_index: u32, dest: &mut [u8]) {
+        let type_str = if input_stream { "input" } else { "state" };
+        // NOTE: We manually append the null terminator '\0' so CStr doesn't panic
+        let file_str = format!("loop_edit_{}_{}.hmi\0", slot_index, type_str);
+        let c_str = ffi::CStr::from_bytes_with_nul(file_str.as_bytes())
+            .expect("Failed to create CStr from file name");
+        self.linux_build_exe_path_file_name(c_str, dest);
+    }
+
+    /// Reserves a snapshot buffer per slot. A slot that can't be set up stays null, and recording
+    /// into it is refused.
+    fn linux_init_replay_buffers(&mut self) {
+        let max_page_count = self.total_size.div_ceil(REPLAY_PAGE_SIZE);
+        for replay_idx in 0..self.replay_buffers.len() {
+            let mut file_name = [0_u8; PATH_MAX_USIZE];
+            self.linux_get_input_file_location(false, replay_idx as u32, &mut file_name);
+
+            // NOTE(aalhendi): anonymous mappings are zero-filled on first touch, so reserving the full
+            //  size up front costs nothing until a snapshot writes into it.
+            let memory_block = linux_allocate_memory(self.total_size);
+            let page_indices = linux_allocate_memory(max_page_count * size_of::<u32>());
+            if memory_block.is_null() || page_indices.is_null() {
+                eprintln!("Failed to allocate replay buffer {replay_idx}");
+                continue;
+            }
+
+            let replay_buffer = &mut self.replay_buffers[replay_idx];
+            replay_buffer.memory_block = memory_block.cast::<()>();
+            replay_buffer.page_indices = page_indices.cast::<u32>();
+            replay_buffer.file_name = file_name;
+        }
+
+        let os_page_count = self.total_size.div_ceil(linux_os_page_size());
+        self.page_map_entries =
+            linux_allocate_memory(os_page_count * size_of::<u64>()).cast::<u64>();
+        self.page_map = match File::open("/proc/self/pagemap") {
+            Ok(file) if !self.page_map_entries.is_null() => Some(file),
+            Ok(_) => None,
+            Err(e) => {
+                eprintln!(
+                    "Failed to open /proc/self/pagemap, snapshots will scan all of memory: {e}"
+                );
+                None
+            }
+        };
+    }
+
+    /// Marks which OS pages of game memory have ever been touched, in `page_map_entries`.
+    /// Returns false if that can't be known, in which case every page has to be treated as touched.
+    #[cfg(feature = "internal_build")]
+    fn linux_read_touched_pages(&self) -> bool {
+        use std::os::unix::fs::FileExt;
+
+        let Some(page_map) = &self.page_map else {
+            return false;
+        };
+        let os_page_size = linux_os_page_size();
+        let os_page_count = self.total_size.div_ceil(os_page_size);
+        let entries = unsafe {
+            core::slice::from_raw_parts_mut(
+                self.page_map_entries.cast::<u8>(),
+                os_page_count * size_of::<u64>(),
+            )
+        };
+        let offset = (self.game_memory_block as usize / os_page_size * size_of::<u64>()) as u64;
+        match page_map.read_exact_at(entries, offset) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("Failed to read /proc/self/pagemap: {e}");
+                false
+            }
+        }
+    }
+
+    /// Copies the non-zero pages of game memory into a slot's replay buffer. Pages the game never
+    /// touched are zero and aren't even looked at.
+    #[cfg(feature = "internal_build")]
+    fn linux_take_snapshot(&mut self, replay_idx: usize) {
+        // NOTE(aalhendi): page is present (bit 63) or swapped out (bit 62).
+        const PAGE_MAP_TOUCHED: u64 = (1 << 63) | (1 << 62);
+
+        let touched_pages_known = self.linux_read_touched_pages();
+        let os_page_size = linux_os_page_size();
+        let game_memory = unsafe {
+            core::slice::from_raw_parts(self.game_memory_block.cast::<u8>(), self.total_size)
+        };
+        let replay_buffer = &mut self.replay_buffers[replay_idx];
+
+        // NOTE(aalhendi): drop the previous snapshot, its pages read back as zero from now on.
+        unsafe {
+            libc::madvise(
+                replay_buffer.memory_block.cast::<ffi::c_void>(),
+                self.total_size,
+                libc::MADV_DONTNEED,
+            );
+        }
+        replay_buffer.page_count = 0;
+        replay_buffer.memory_hash = REPLAY_EMPTY_MEMORY_HASH;
+
+        for (os_page_index, os_page) in game_memory.chunks(os_page_size).enumerate() {
+            if touched_pages_known
+                && unsafe { *self.page_map_entries.add(os_page_index) } & PAGE_MAP_TOUCHED == 0
+            {
+                continue;
+            }
+
+            let first_page_index = os_page_index * os_page_size / REPLAY_PAGE_SIZE;
+            for (page_offset, page) in os_page.chunks(REPLAY_PAGE_SIZE).enumerate() {
+                if replay_page_is_zero(page) {
+                    continue;
+                }
+                let page_index = (first_page_index + page_offset) as u32;
+                unsafe {
+                    ptr::copy_nonoverlapping(
+                        page.as_ptr(),
+                        replay_buffer
+                            .memory_block
+                            .cast::<u8>()
+                            .add(page_index as usize * REPLAY_PAGE_SIZE),
+                        page.len(),
+                    );
+                    *replay_buffer
+                        .page_indices
+                        .add(replay_buffer.page_count as usize) = page_index;
+                }
+                replay_buffer.page_count += 1;
+                replay_buffer.memory_hash =
+                    replay_hash_page(replay_buffer.memory_hash, page_index, page);
+            }
+        }
+    }
+
+    /// Zeroes game memory and copies a slot's snapshot pages back into it.
+    fn linux_restore_snapshot(&self, replay_idx: usize) {
+        let replay_buffer = &self.replay_buffers[replay_idx];
+        unsafe {
+            // NOTE(aalhendi): private anonymous pages read back as zero after MADV_DONTNEED, and pages
+            //  the game never touched don't cost anything.
+            libc::madvise(
+                self.game_memory_block.cast::<ffi::c_void>(),
+                self.total_size,
+                libc::MADV_DONTNEED,
+            );
+            for page in 0..replay_buffer.page_count as usize {
+                let offset = *replay_buffer.page_indices.add(page) as usize * REPLAY_PAGE_SIZE;
+                ptr::copy_nonoverlapping(
+                    replay_buffer.memory_block.cast::<u8>().add(offset),
+                    self.game_memory_block.cast::<u8>().add(offset),
+                    REPLAY_PAGE_SIZE.min(self.total_size - offset),
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "internal_build")]
+    fn linux_begin_recording_input(&mut self, input_recording_idx: u32) {
+        // TODO(aalhendi): These files must go in a temp/build directory!
+        debug_assert!(input_recording_idx < self.replay_buffers.len() as u32);
+        let replay_memory_block = self.replay_buffers[input_recording_idx as usize].memory_block;
+
+        if replay_memory_block.is_null() {
+            println!("No replay buffer found!");
+            return;
+        }
+
+        let mut file_name = [0_u8; PATH_MAX_USIZE];
+        self.linux_get_input_file_location(true, input_recording_idx, &mut file_name);
+        let file_name = ffi::CStr::from_bytes_until_nul(&file_name)
+            .expect("Failed to create CStr from input file name");
+
+        let mut recording_file = match File::create(linux_path(file_name)) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("Failed to create recording file {file_name:?}: {e}");
+                return;
+            }
+        };
+
+        // NOTE(aalhendi): take memory snapshot (Copy from Game -> Replay Buffer)
+        self.linux_take_snapshot(input_recording_idx as usize);
+        let replay_buffer = &self.replay_buffers[input_recording_idx as usize];
+        let state_file_name = ffi::CStr::from_bytes_until_nul(&replay_buffer.file_name)
+            .expect("Failed to create CStr from replay file name");
+        if let Err(e) = linux_write_snapshot_file(replay_buffer, self.total_size, state_file_name) {
+            // NOTE(aalhendi): the snapshot in memory is all playback needs, the file is for tools.
+            eprintln!("Failed to write replay snapshot {state_file_name:?}: {e}");
+        }
+
+        let memory_hash = replay_buffer.memory_hash;
+        self.recording_header = ReplayHeader::new(0.0, memory_hash);
+        if let Err(e) = recording_file.write_all(&self.recording_header.to_bytes()) {
+            eprintln!("Failed to write recording header {file_name:?}: {e}");
+            return;
+        }
+
+        self.recording_file = Some(recording_file);
+        self.input_recording_idx = Some(input_recording_idx);
+        println!("Recording into replay slot {}", input_recording_idx + 1);
+    }
+
+    #[cfg(feature = "internal_build")]
+    fn linux_end_recording_input(&mut self) {
+        if let Some(mut recording_file) = self.recording_file.take() {
+            let header_bytes = self.recording_header.to_bytes();
+            if let Err(e) = recording_file
+                .seek(SeekFrom::Start(0))
+                .and_then(|_| recording_file.write_all(&header_bytes))
+            {
+                eprintln!("Failed to finish recording header: {e}");
+            }
+        }
+        self.input_recording_idx = None;
+    }
+
+    #[cfg(feature = "internal_build")]
+    fn linux_begin_input_playback(&mut self, input_playing_idx: u32) {
+        let mut file_name = [0_u8; PATH_MAX_USIZE];
+        self.linux_get_input_file_location(true, input_playing_idx, &mut file_name);
+        let file_name = ffi::CStr::from_bytes_until_nul(&file_name)
+            .expect("Failed to create CStr from input file name");
+
+        if let Err(e) =
+            self.linux_begin_input_playback_from(input_playing_idx, linux_path(file_name))
+        {
+            eprintln!("{e}");
+        }
+    }
+
+    /// Loops the input stream at `path`, starting from the snapshot held in `input_playing_idx`.
+    #[cfg(feature = "internal_build")]
+    fn linux_begin_input_playback_from(
+        &mut self,
+        input_playing_idx: u32,
+        path: &OsStr,
+    ) -> Result<(), String> {
+        debug_assert!(input_playing_idx < self.replay_buffers.len() as u32);
+        let replay_memory_block = self.replay_buffers[input_playing_idx as usize].memory_block;
+        if replay_memory_block.is_null() {
+            return Err("No replay buffer found!".to_string());
+        }
+
+        let mut playback_file =
+            File::open(path).map_err(|e| format!("Failed to open playback file {path:?}: {e}"))?;
+        let header = linux_read_replay_header(&mut playback_file)
+            .map_err(|e| format!("Refusing to play back {path:?}: {e}"))?;
+        if header.memory_hash != 0 {
+            let memory_hash = self.replay_buffers[input_playing_idx as usize].memory_hash;
+            if memory_hash != header.memory_hash {
+                return Err(format!(
+                    "Refusing to play back {path:?}: it was recorded from a different memory snapshot"
+                ));
+            }
+        }
+
+        self.playback_data_offset = header.data_offset();
+        self.playback_file = Some(playback_file);
+        self.input_playing_idx = Some(input_playing_idx);
+        println!("Looping replay slot {}", input_playing_idx + 1);
+
+        // NOTE(aalhendi): restore the memory snapshot (Copy from Replay Buffer -> Game)
+        self.linux_restore_snapshot(input_playing_idx as usize);
+        Ok(())
+    }
+
+    #[cfg(feature = "internal_build")]
+    fn linux_end_input_playback(&mut self) {
+        self.playback_file = None;
+        self.input_playing_idx = None;
+    }
+
+    /// Stops whatever is recording or playing and loops `replay_idx`, or stops it if it is already looping.
+    #[cfg(feature = "internal_build")]
+    fn linux_toggle_input_playback(&mut self, replay_idx: u32) {
+        let was_playing = self.input_playing_idx == Some(replay_idx);
+        if self.input_recording_idx.is_some() {
+            self.linux_end_recording_input();
+        }
+        if self.input_playing_idx.is_some() {
+            self.linux_end_input_playback();
+        }
+        if !was_playing {
+            self.linux_begin_input_playback(replay_idx);
+        }
+    }
+
+    /// Stops whatever is recording or playing and records into `replay_idx`, or stops it if it is
+    /// already recording.
+    #[cfg(feature = "internal_build")]
+    fn linux_toggle_input_recording(&mut self, replay_idx: u32) {
+        let was_recording = self.input_recording_idx == Some(replay_idx);
+        if self.input_recording_idx.is_some() {
+            self.linux_end_recording_input();
+        }
+        if self.input_playing_idx.is_some() {
+            self.linux_end_input_playback();
+        }
+        if !was_recording {
+            self.linux_begin_recording_input(replay_idx);
+        }
+    }
+
+    fn linux_record_input(&mut self, new_input: &mut GameInput) {
+        let Some(recording_file) = &mut self.recording_file else {
+            return;
+        };
+        let input_bytes = unsafe {
+            core::slice::from_raw_parts(
+                (new_input as *const GameInput).cast::<u8>(),
+                size_of::<GameInput>(),
+            )
+        };
+        if let Err(e) = recording_file.write_all(input_bytes) {
+            eprintln!("Failed to write into recording file: {e}");
+            return;
+        }
+
+        if self.recording_header.frame_count == 0 {
+            self.recording_header.dt_for_frame = new_input.dt_for_frame;
+        }
+        self.recording_header.frame_count += 1;
+    }
+
+    fn linux_playback_input(&mut self, new_input: &mut GameInput) {
+        let Some(playback_file) = &mut self.playback_file else {
+            return;
+        };
+        let input_bytes = unsafe {
+            core::slice::from_raw_parts_mut(
+                (new_input as *mut GameInput).cast::<u8>(),
+                size_of::<GameInput>(),
+            )
+        };
+        match playback_file.read_exact(input_bytes) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                // NOTE(aalhendi): we've hit the end of the stream, go back to the beginning. The
+                //  snapshot was checked against the header when playback began, no need to again.
+                if let Some(input_playing_idx) = self.input_playing_idx {
+                    self.linux_restore_snapshot(input_playing_idx as usize);
+                }
+                let Some(playback_file) = &mut self.playback_file else {
+                    return;
+                };
+                if let Err(e) = playback_file
+                    .seek(SeekFrom::Start(self.playback_data_offset))
+                    .and_then(|_| playback_file.read_exact(input_bytes))
+                {
+                    eprintln!("Failed to restart playback: {e}");
+                }
+            }
+            Err(e) => eprintln!("Failed to read from playback file: {e}"),
+        }
+    }
+
+    fn linux_get_exe_file_name(&mut self) {
+        let buffer_ptr = self.exe_file_name.as_mut_ptr().cast::<ffi::c_char>();
+        let buffer_len = self.exe_file_name.len();
+        // NOTE(aalhendi): readlink does not null terminate, the buffer is zeroed so we leave room for one.
+        let file_name_size =
+            unsafe { libc::readlink(c"/proc/self/exe".as_ptr(), buffer_ptr, buffer_len - 1) };
+
+        // NOTE(aalhendi): check for failure or partial path. They are both useless.
+        if file_name_size <= 0 || file_name_size as usize >= buffer_len - 1 {
+            eprintln!("Error: readlink(/proc/self/exe) failed");
+            self.exe_file_name_base_offset = 0;
+            return;
+        }
+
+        if let Some(pos) = self.exe_file_name[..file_name_size as usize]
+            .iter()
+            .rposition(|&c| c == b'/')
+        {
+            self.exe_file_name_base_offset = pos + 1;
+        } else {
+            self.exe_file_name_base_offset = 0;
+        }
+    }
+
+    fn linux_build_exe_path_file_name(&self, file_name: &ffi::CStr, dest: &mut [u8]) {
+        let base_len = self.exe_file_name_base_offset;
+        let file_bytes = file_name.to_bytes_with_nul();
+
+        debug_assert!(
+            base_len + file_bytes.len() <= dest.len(),
+            "Path buffer too small!"
+        );
+
+        dest[..base_len].copy_from_slice(&self.exe_file_name[..base_len]);
+
+        let end_idx = base_len + file_bytes.len();
+        dest[base_len..end_idx].copy_from_slice(file_bytes);
+    }
+}
+
+#[inline]
+#[cfg(feature = "internal_build")]
+/// Reads and validates the header of a replay input stream, leaving the file at the first frame.
+fn linux_read_replay_header(file: &mut File) -> Result<ReplayHeader, String> {
+    let stream_size = file.metadata().map_err(|e| e.to_string())?.len();
+    let mut prefix = [0_u8; ReplayHeader::SIZE];
+    let prefix_len = (stream_size as usize).min(prefix.len());
+    file.read_exact(&mut prefix[..prefix_len])
+        .map_err(|e| e.to_string())?;
+
+    let header =
+        ReplayHeader::parse(&prefix[..prefix_len], stream_size).map_err(|e| e.to_string())?;
+    file.seek(SeekFrom::Start(header.data_offset()))
+        .map_err(|e| e.to_string())?;
+    Ok(header)
+}
+
+/// Writes a `ReplaySnapshotHeader`, the page index list and then the pages themselves.
+#[cfg(feature = "internal_build")]
+fn linux_write_snapshot_file(
+    replay_buffer: &LinuxReplayBuffer,
+    total_size: usize,
+    file_name: &ffi::CStr,
+) -> io::Result<()> {
+    let mut file = io::BufWriter::new(File::create(linux_path(file_name))?);
+    let header = ReplaySnapshotHeader::new(
+        total_size as u64,
+        replay_buffer.page_count,
+        replay_buffer.memory_hash,
+    );
+    file.write_all(&header.to_bytes())?;
+
+    let page_indices = unsafe {
+        core::slice::from_raw_parts(
+            replay_buffer.page_indices,
+            replay_buffer.page_count as usize,
+        )
+    };
+    for page_index in page_indices {
+        file.write_all(&page_index.to_le_bytes())?;
+    }
+    for &page_index in page_indices {
+        let offset = page_index as usize * REPLAY_PAGE_SIZE;
+        let page = unsafe {
+            core::slice::from_raw_parts(
+                replay_buffer.memory_block.cast::<u8>().add(offset),
+                REPLAY_PAGE_SIZE.min(total_size - offset),
+            )
+        };
+        file.write_all(page)?;
+    }
+    file.flush()
+}
+
+fn linux_os_page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+struct LinuxOffscreenBuffer {
+    // NOTE(aalhendi): pixels are always 32-bits wide, Memory Order BB GG RR XX.
+    //  This happens to be the 0RGB layout softbuffer wants, so we can copy rows straight across.
+    memory: *mut ffi::c_void,
+    width: i32,
+    height: i32,
+    pitch: i32,
+    bytes_per_pixel: i32,
+}
+
+impl LinuxOffscreenBuffer {
+    fn linux_resize_backbuffer(&mut self, width: i32, height: i32) {
+        if !self.memory.is_null() {
+            let old_size = (self.bytes_per_pixel * self.width * self.height) as usize;
+            let unmap_res = unsafe { libc::munmap(self.memory, old_size) };
+            if unmap_res != 0 {
+                panic!("Failed to free backbuffer memory");
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+
+        let bitmap_memory_size = (self.bytes_per_pixel * self.width * self.height) as usize;
+        self.memory = linux_allocate_memory(bitmap_memory_size);
+        if self.memory.is_null() {
+            panic!("Failed to allocate backbuffer memory");
+        }
+
+        self.pitch = width * self.bytes_per_pixel;
+    }
+
+    /// Maps a window-space cursor position (physical pixels) to backbuffer pixels.
+    /// NOTE(aalhendi): must stay in sync with how linux_copy_buffer_to_window lays the buffer out.
+    ///  We blit 1-1 at the top-left corner, so this is only a truncation for now.
+    fn linux_window_to_backbuffer(&self, window_x: f64, window_y: f64) -> (i32, i32) {
+        (window_x.floor() as i32, window_y.floor() as i32)
+    }
+
+    fn linux_copy_buffer_to_window(&self, dest: &mut [u32], window_width: u32, window_height: u32) {
+        // NOTE(aalhendi): for prototyping purposes, we're going to always blit 1-1 pixels to make sure we don't introduce artifacts
+        //  with stretching until we get a decent renderer
+        let window_width = window_width as usize;
+        let copy_width = (self.width as usize).min(window_width);
+        let copy_height = (self.height as u32).min(window_height) as usize;
+
+        for (y, dest_row) in dest.chunks_exact_mut(window_width).enumerate() {
+            if y < copy_height {
+                let source_row = unsafe {
+                    core::slice::from_raw_parts(
+                        self.memory
+                            .cast::<u8>()
+                            .add(y * self.pitch as usize)
+                            .cast::<u32>(),
+                        copy_width,
+                    )
+                };
+                dest_row[..copy_width].copy_from_slice(source_row);
+                dest_row[copy_width..].fill(0);
+            } else {
+                dest_row.fill(0);
+            }
+        }
+    }
+}
+
+fn linux_process_keyboard_message(new_state: &mut GameButtonState, is_down: bool) {
+    if new_state.ended_down != is_down {
+        new_state.ended_down = is_down;
+        new_state.half_transition_count += 1;
+    }
+}
+
+enum LinuxSoundSink {
+    Alsa(LinuxAlsaSink),
+    WavFile(LinuxWavFileSink),
+    // NOTE(aalhendi): no device and nowhere to write. We still pull samples so the game runs the same.
+    Null,
+}
+
+struct LinuxAlsaSink {
+    lib: alsa::AlsaLib,
+    pcm: *mut alsa::snd_pcm_t,
+}
+
+impl Drop for LinuxAlsaSink {
+    fn drop(&mut self) {
+        unsafe {
+            (self.lib.snd_pcm_close)(self.pcm);
+        }
+    }
+}
+
+struct LinuxSoundOutput {
+    samples_per_second: u32,
+    bytes_per_sample: u32,
+    buffer_size: u32,
+    // NOTE(aalhendi): frames rarely line up with whole samples (48000 / 144hz), this carries the fraction.
+    frame_sample_remainder: f64,
+    safety_samples: u32,
+    samples: *mut i16,
+    sink: LinuxSoundSink,
+}
+
+impl LinuxSoundOutput {
+    fn new(samples_per_second: u32, game_update_hz: u32) -> Self {
+        let bytes_per_sample = size_of::<i16>() as u32 * 2;
+        let buffer_size = samples_per_second * bytes_per_sample; // 2 channels, 2 bytes per sample
+
+        // TODO(aalhendi): pool with bitmap allocation
+        let samples = linux_allocate_memory(buffer_size as usize).cast::<i16>();
+        if samples.is_null() {
+            panic!("Failed to allocate sound samples");
+        }
+
+        let mut sound_output = Self {
+            samples_per_second,
+            bytes_per_sample,
+            buffer_size,
+            frame_sample_remainder: 0.0,
+            safety_samples: 0,
+            samples,
+            sink: linux_init_sound_sink(samples_per_second, bytes_per_sample),
+        };
+        sound_output.linux_set_game_update_hz(game_update_hz);
+        sound_output
+    }
+
+    fn linux_set_game_update_hz(&mut self, game_update_hz: u32) {
+        // TODO(aalhendi): actually compute this variance and see lowest reasonable value
+        self.safety_samples = (self.samples_per_second / game_update_hz) / 3;
+    }
+
+    /// How many samples the sink wants from the game this frame.
+    fn linux_get_sample_count(&mut self, seconds_per_frame: f64) -> u32 {
+        let exact_samples =
+            self.samples_per_second as f64 * seconds_per_frame + self.frame_sample_remainder;
+        let samples_per_frame = exact_samples.floor();
+        self.frame_sample_remainder = exact_samples - samples_per_frame;
+        let samples_per_frame = samples_per_frame as u32;
+        let safety_samples = self.safety_samples;
+
+        let sample_count = match &mut self.sink {
+            LinuxSoundSink::Alsa(sink) => unsafe {
+                let avail = (sink.lib.snd_pcm_avail_update)(sink.pcm);
+                if avail < 0 {
+                    (sink.lib.snd_pcm_recover)(sink.pcm, avail as ffi::c_int, 1);
+                }
+
+                // NOTE(aalhendi): queue up enough to last until the next frame, plus a little in case we're late.
+                let mut delay: alsa::snd_pcm_sframes_t = 0;
+                if (sink.lib.snd_pcm_delay)(sink.pcm, &mut delay) < 0 {
+                    delay = 0;
+                }
+                let target_queued = (samples_per_frame + safety_samples) as alsa::snd_pcm_sframes_t;
+                (target_queued - delay).clamp(0, avail.max(0)) as u32
+            },
+            LinuxSoundSink::WavFile(_) | LinuxSoundSink::Null => samples_per_frame,
+        };
+
+        sample_count.min(self.buffer_size / self.bytes_per_sample)
+    }
+
+    fn linux_fill_sound_buffer(&mut self, source_buffer: &GameSoundOutputBuffer) {
+        match &mut self.sink {
+            LinuxSoundSink::Alsa(sink) => unsafe {
+                let mut written = (sink.lib.snd_pcm_writei)(
+                    sink.pcm,
+                    source_buffer.samples.cast::<ffi::c_void>(),
+                    source_buffer.sample_count as alsa::snd_pcm_uframes_t,
+                );
+                if written < 0 {
+                    // NOTE(aalhendi): most likely an underrun (-EPIPE). Recover and give it one more go.
+                    (sink.lib.snd_pcm_recover)(sink.pcm, written as ffi::c_int, 1);
+                    written = (sink.lib.snd_pcm_writei)(
+                        sink.pcm,
+                        source_buffer.samples.cast::<ffi::c_void>(),
+                        source_buffer.sample_count as alsa::snd_pcm_uframes_t,
+                    );
+                }
+                if written < 0 {
+                    eprintln!(
+                        "ALSA write failed: {}",
+                        ffi::CStr::from_ptr((sink.lib.snd_strerror)(written as ffi::c_int))
+                            .to_string_lossy()
+                    );
+                }
+            },
+            LinuxSoundSink::WavFile(sink) => {
+                if let Err(e) = sink.write_samples(source_buffer) {
+                    eprintln!("Failed to write WAV samples: {e}");
+                }
+            }
+            LinuxSoundSink::Null => {}
+        }
+    }
+}
+
+/// Picks where sound goes. HM_WAV_OUTPUT=<path> always writes a WAV file (handy on machines with no
+/// sound card), otherwise we try ALSA's default device and give up quietly if there isn't one.
+fn linux_init_sound_sink(samples_per_second: u32, bytes_per_sample: u32) -> LinuxSoundSink {
+    if let Some(wav_path) = std::env::var_os("HM_WAV_OUTPUT") {
+        match LinuxWavFileSink::create(&wav_path, samples_per_second, bytes_per_sample) {
+            Ok(sink) => return LinuxSoundSink::WavFile(sink),
+            Err(e) => eprintln!("Failed to create WAV output {wav_path:?}: {e}"),
+        }
+    }
+
+    match linux_init_alsa(samples_per_second) {
+        Ok(sink) => LinuxSoundSink::Alsa(sink),
+        Err(e) => {
+            eprintln!("Failed to initialize ALSA, sound is disabled: {e}");
+            LinuxSoundSink::Null
+        }
+    }
+}
+
+fn linux_init_alsa(samples_per_second: u32) -> Result<LinuxAlsaSink, String> {
+    use alsa::*;
+
+    unsafe {
+        let lib = AlsaLib::load()?;
+        let alsa_error = |error: ffi::c_int| {
+            ffi::CStr::from_ptr((lib.snd_strerror)(error))
+                .to_string_lossy()
+                .into_owned()
+        };
+
+        let mut pcm: *mut snd_pcm_t = ptr::null_mut();
+        let open_result = (lib.snd_pcm_open)(
+            &mut pcm,
+            c"default".as_ptr(),
+            SND_PCM_STREAM_PLAYBACK,
+            SND_PCM_NONBLOCK,
+        );
+        if open_result < 0 {
+            return Err(format!("snd_pcm_open failed: {}", alsa_error(open_result)));
+        }
+
+        // NOTE(aalhendi): the device buffer only has to cover a few frames, we top it up every frame.
+        let latency_us = 100_000;
+        let params_result = (lib.snd_pcm_set_params)(
+            pcm,
+            SND_PCM_FORMAT_S16_LE,
+            SND_PCM_ACCESS_RW_INTERLEAVED,
+            2,
+            samples_per_second,
+            1,
+            latency_us,
+        );
+        if params_result < 0 {
+            (lib.snd_pcm_close)(pcm);
+            return Err(format!(
+                "snd_pcm_set_params failed: {}",
+                alsa_error(params_result)
+            ));
+        }
+
+        Ok(LinuxAlsaSink { lib, pcm })
+    }
+}
+
+/// Which of a pad's digital inputs are held, one bit per LINUX_GAMEPAD_* flag.
+type LinuxGamepadButtonFlags = u16;
+
+const LINUX_GAMEPAD_DPAD_UP: LinuxGamepadButtonFlags = 1 << 0;
+const LINUX_GAMEPAD_DPAD_DOWN: LinuxGamepadButtonFlags = 1 << 1;
+const LINUX_GAMEPAD_DPAD_LEFT: LinuxGamepadButtonFlags = 1 << 2;
+const LINUX_GAMEPAD_DPAD_RIGHT: LinuxGamepadButtonFlags = 1 << 3;
+const LINUX_GAMEPAD_START: LinuxGamepadButtonFlags = 1 << 4;
+const LINUX_GAMEPAD_BACK: LinuxGamepadButtonFlags = 1 << 5;
+const LINUX_GAMEPAD_LEFT_SHOULDER: LinuxGamepadButtonFlags = 1 << 6;
+const LINUX_GAMEPAD_RIGHT_SHOULDER: LinuxGamepadButtonFlags = 1 << 7;
+const LINUX_GAMEPAD_SOUTH: LinuxGamepadButtonFlags = 1 << 8;
+const LINUX_GAMEPAD_EAST: LinuxGamepadButtonFlags = 1 << 9;
+const LINUX_GAMEPAD_WEST: LinuxGamepadButtonFlags = 1 << 10;
+const LINUX_GAMEPAD_NORTH: LinuxGamepadButtonFlags = 1 << 11;
+
+#[derive(Clone, Copy)]
+struct LinuxAxisRange {
+    minimum: i32,
+    maximum: i32,
+}
+
+impl Default for LinuxAxisRange {
+    fn default() -> Self {
+        // NOTE(aalhendi): what xpad reports. Real ranges come from EVIOCGABS when the pad is opened.
+        Self {
+            minimum: -32768,
+            maximum: 32767,
+        }
+    }
+}
+
+/// The latest known state of one evdev gamepad, built up from its event stream.
+#[derive(Default, Clone, Copy)]
+struct LinuxGamepadState {
+    left_stick_x: i32,
+    left_stick_y: i32,
+    right_stick_x: i32,
+    right_stick_y: i32,
+    left_stick_x_range: LinuxAxisRange,
+    left_stick_y_range: LinuxAxisRange,
+    right_stick_x_range: LinuxAxisRange,
+    right_stick_y_range: LinuxAxisRange,
+    // NOTE(aalhendi): pads report the d-pad as either a hat (-1/0/1) or as BTN_DPAD_* keys.
+    hat_x: i32,
+    hat_y: i32,
+    buttons: LinuxGamepadButtonFlags,
+}
+
+impl LinuxGamepadState {
+    fn linux_apply_event(&mut self, event_type: u16, code: u16, value: i32) {
+        match event_type {
+            evdev::EV_KEY => {
+                let flag = match code {
+                    evdev::BTN_SOUTH => LINUX_GAMEPAD_SOUTH,
+                    evdev::BTN_EAST => LINUX_GAMEPAD_EAST,
+                    evdev::BTN_NORTH => LINUX_GAMEPAD_NORTH,
+                    evdev::BTN_WEST => LINUX_GAMEPAD_WEST,
+                    evdev::BTN_TL => LINUX_GAMEPAD_LEFT_SHOULDER,
+                    evdev::BTN_TR => LINUX_GAMEPAD_RIGHT_SHOULDER,
+                    evdev::BTN_SELECT => LINUX_GAMEPAD_BACK,
+                    evdev::BTN_START => LINUX_GAMEPAD_START,
+                    evdev::BTN_DPAD_UP => LINUX_GAMEPAD_DPAD_UP,
+                    evdev::BTN_DPAD_DOWN => LINUX_GAMEPAD_DPAD_DOWN,
+                    evdev::BTN_DPAD_LEFT => LINUX_GAMEPAD_DPAD_LEFT,
+                    evdev::BTN_DPAD_RIGHT => LINUX_GAMEPAD_DPAD_RIGHT,
+                    _ => return,
+                };
+                // NOTE(aalhendi): 0 is release, 1 is press, 2 is autorepeat (still held).
+                if value != 0 {
+                    self.buttons |= flag;
+                } else {
+                    self.buttons &= !flag;
+                }
+            }
+            evdev::EV_ABS => match code {
+                evdev::ABS_X => self.left_stick_x = value,
+                evdev::ABS_Y => self.left_stick_y = value,
+                evdev::ABS_RX => self.right_stick_x = value,
+                evdev::ABS_RY => self.right_stick_y = value,
+                evdev::ABS_HAT0X => self.hat_x = value,
+                evdev::ABS_HAT0Y => self.hat_y = value,
+                _ => {}
+            },
+            // TODO(aalhendi): SYN_DROPPED means the kernel buffer overflowed and we should re-query
+            //  everything with EVIOCGKEY/EVIOCGABS. Pads don't send enough to hit it at our frame rate.
+            _ => {}
+        }
+    }
+
+    fn linux_dpad_buttons(&self) -> LinuxGamepadButtonFlags {
+        let mut buttons = self.buttons;
+        if self.hat_y < 0 {
+            buttons |= LINUX_GAMEPAD_DPAD_UP;
+        }
+        if self.hat_y > 0 {
+            buttons |= LINUX_GAMEPAD_DPAD_DOWN;
+        }
+        if self.hat_x < 0 {
+            buttons |= LINUX_GAMEPAD_DPAD_LEFT;
+        }
+        if self.hat_x > 0 {
+            buttons |= LINUX_GAMEPAD_DPAD_RIGHT;
+        }
+        buttons
+    }
+}
+
+/// Decodes whatever evdev events `reader` has ready into `state`.
+/// Returns Ok once the reader runs dry (WouldBlock for a device, EOF for a recorded stream),
+/// and Err if the device went away.
+fn linux_read_gamepad_events<R: Read>(
+    reader: &mut R,
+    state: &mut LinuxGamepadState,
+) -> io::Result<()> {
+    const EVENT_SIZE: usize = size_of::<libc::input_event>();
+    let mut events = [0_u8; EVENT_SIZE * 64];
+
+    loop {
+        let bytes_read = match reader.read(&mut events) {
+            Ok(0) => return Ok(()),
+            Ok(bytes_read) => bytes_read,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        // NOTE(aalhendi): evdev only ever hands out whole events.
+        debug_assert!(bytes_read % EVENT_SIZE == 0, "Partial evdev event");
+        for event_bytes in events[..bytes_read].chunks_exact(EVENT_SIZE) {
+            let event =
+                unsafe { ptr::read_unaligned(event_bytes.as_ptr().cast::<libc::input_event>()) };
+            state.linux_apply_event(event.type_, event.code, event.value);
+        }
+    }
+}
+
+#[derive(Default)]
+struct LinuxGamepad {
+    // NOTE(aalhendi): None means this slot has nothing plugged in.
+    file: Option<File>,
+    path: PathBuf,
+    state: LinuxGamepadState,
+}
+
+/// Opens `path` if it is an evdev device that looks like a gamepad.
+fn linux_open_gamepad(path: PathBuf) -> Option<LinuxGamepad> {
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(&path)
+        .ok()?;
+    let fd = file.as_raw_fd();
+
+    let mut key_bits = [0_u8; evdev::KEY_MAX / 8 + 1];
+    let ioctl_result = unsafe {
+        libc::ioctl(
+            fd,
+            evdev::eviocgbit(evdev::EV_KEY, key_bits.len()),
+            key_bits.as_mut_ptr(),
+        )
+    };
+    let has_key = |code: u16| key_bits[code as usize / 8] & (1 << (code % 8)) != 0;
+    if ioctl_result < 0 || !has_key(evdev::BTN_SOUTH) {
+        return None;
+    }
+
+    let axis_range = |axis: u16| {
+        let mut info = unsafe { mem::zeroed::<libc::input_absinfo>() };
+        let ioctl_result = unsafe { libc::ioctl(fd, evdev::eviocgabs(axis), &mut info) };
+        if ioctl_result < 0 || info.minimum >= info.maximum {
+            (LinuxAxisRange::default(), 0)
+        } else {
+            (
+                LinuxAxisRange {
+                    minimum: info.minimum,
+                    maximum: info.maximum,
+                },
+                info.value,
+            )
+        }
+    };
+
+    let mut state = LinuxGamepadState::default();
+    (state.left_stick_x_range, state.left_stick_x) = axis_range(evdev::ABS_X);
+    (state.left_stick_y_range, state.left_stick_y) = axis_range(evdev::ABS_Y);
+    (state.right_stick_x_range, state.right_stick_x) = axis_range(evdev::ABS_RX);
+    (state.right_stick_y_range, state.right_stick_y) = axis_range(evdev::ABS_RY);
+
+    Some(LinuxGamepad {
+        file: Some(file),
+        path,
+        state,
+    })
+}
+
+fn linux_process_stick_value(value: i32, range: LinuxAxisRange, deadzone_threshold: f32) -> f32 {
+    // NOTE(aalhendi): pads disagree on ranges (xpad is i16, plenty are 0..255), so normalize to -1..1 first.
+    let center = (range.minimum as f32 + range.maximum as f32) / 2_f32;
+    let half_range = (range.maximum as f32 - range.minimum as f32) / 2_f32;
+    let value = ((value as f32 - center) / half_range).clamp(-1_f32, 1_f32);
+
+    if value < -deadzone_threshold {
+        (value + deadzone_threshold) / (1_f32 - deadzone_threshold)
+    } else if value > deadzone_threshold {
+        (value - deadzone_threshold) / (1_f32 - deadzone_threshold)
+    } else {
+        0_f32
+    }
+}
+
+/// The key bindings call `event`'s key. Letters go by what the layout types, the rest by where
+/// they are, like virtual keys on Windows.
+fn linux_binding_key(event: &KeyEvent) -> Option<InputKey> {
+    if let Key::Character(text) = event.key_without_modifiers() {
+        let mut chars = text.chars();
+        if let (Some(c), None) = (chars.next(), chars.next())
+            && c.is_ascii_alphabetic()
+        {
+            return InputKey::from_char(c);
+        }
+    }
+    let PhysicalKey::Code(key_code) = event.physical_key else {
+        return None;
+    };
+    let key = match key_code {
+        KeyCode::Digit0 => InputKey::Character(b'0'),
+        KeyCode::Digit1 => InputKey::Character(b'1'),
+        KeyCode::Digit2 => InputKey::Character(b'2'),
+        KeyCode::Digit3 => InputKey::Character(b'3'),
+        KeyCode::Digit4 => InputKey::Character(b'4'),
+        KeyCode::Digit5 => InputKey::Character(b'5'),
+        KeyCode::Digit6 => InputKey::Character(b'6'),
+        KeyCode::Digit7 => InputKey::Character(b'7'),
+        KeyCode::Digit8 => InputKey::Character(b'8'),
+        KeyCode::Digit9 => InputKey::Character(b'9'),
+        KeyCode::ArrowUp => InputKey::Up,
+        KeyCode::ArrowDown => InputKey::Down,
+        KeyCode::ArrowLeft => InputKey::Left,
+        KeyCode::ArrowRight => InputKey::Right,
+        KeyCode::Space => InputKey::Space,
+        KeyCode::Enter | KeyCode::NumpadEnter => InputKey::Enter,
+        KeyCode::Escape => InputKey::Escape,
+        KeyCode::Tab => InputKey::Tab,
+        KeyCode::Backspace => InputKey::Backspace,
+        KeyCode::ShiftLeft | KeyCode::ShiftRight => InputKey::Shift,
+        KeyCode::ControlLeft | KeyCode::ControlRight => InputKey::Control,
+        KeyCode::AltLeft | KeyCode::AltRight => InputKey::Alt,
+        KeyCode::F1 => InputKey::Function(1),
+        KeyCode::F2 => InputKey::Function(2),
+        KeyCode::F3 => InputKey::Function(3),
+        KeyCode::F4 => InputKey::Function(4),
+        KeyCode::F5 => InputKey::Function(5),
+        KeyCode::F6 => InputKey::Function(6),
+        KeyCode::F7 => InputKey::Function(7),
+        KeyCode::F8 => InputKey::Function(8),
+        KeyCode::F9 => InputKey::Function(9),
+        KeyCode::F10 => InputKey::Function(10),
+        KeyCode::F11 => InputKey::Function(11),
+        KeyCode::F12 => InputKey::Function(12),
+        _ => return None,
+    };
+    Some(key)
+}
+
+fn linux_process_gamepad_digital_button(
+    button_state: LinuxGamepadButtonFlags,
+    old_state: &mut GameButtonState,
+    button_bit: LinuxGamepadButtonFlags,
+    new_state: &mut GameButtonState,
+) {
+    new_state.ended_down = (button_state & button_bit) == button_bit;
+    new_state.half_transition_count = if old_state.ended_down != new_state.ended_down {
+        1
+    } else {
+        0
+    };
+}
+
+impl LinuxApp {
+    fn linux_set_monitor_refresh_hz(&mut self, monitor_refresh_hz: u32) {
+        self.game_update_hz = monitor_refresh_hz as f32 / 2_f32;
+        self.target_seconds_per_frame = 1_f64 / self.game_update_hz as f64;
+        self.sound_output
+            .linux_set_game_update_hz(self.game_update_hz as u32);
+        println!(
+            "Monitor refresh rate: {monitor_refresh_hz}hz, game update rate: {}hz",
+            self.game_update_hz
+        );
+    }
+
+    /// Sleeps, then spins, until the current frame has used up its time budget.
+    fn linux_wait_for_frame_end(&mut self) {
+        let work_counter = linux_get_wall_clock();
+        let work_seconds_elapsed = linux_get_seconds_elapsed(self.last_counter, work_counter);
+
+        let mut seconds_elapsed_for_frame = work_seconds_elapsed;
+        if seconds_elapsed_for_frame < self.target_seconds_per_frame {
+            while seconds_elapsed_for_frame < self.target_seconds_per_frame {
+                // NOTE(aalhendi): nanosleep tends to overshoot by tens of microseconds, so we stop a
+                //  millisecond short and spin for the rest.
+                let sleep_seconds = self.target_seconds_per_frame - seconds_elapsed_for_frame;
+                if sleep_seconds > 0.001 {
+                    std::thread::sleep(Duration::from_secs_f64(sleep_seconds - 0.001));
+                }
+
+                seconds_elapsed_for_frame =
+                    linux_get_seconds_elapsed(self.last_counter, linux_get_wall_clock());
+            }
+        } else {
+            // TODO(aalhendi): handle missed frame
+            println!(
+                "MISSED TARGET FPS!!! {seconds_elapsed_for_frame} > {}",
+                self.target_seconds_per_frame
+            );
+        }
+
+        self.last_counter = linux_get_wall_clock();
+    }
+
+    /// Opens any gamepads that were plugged in since the last scan.
+    fn linux_scan_for_gamepads(&mut self) {
+        let Ok(entries) = std::fs::read_dir("/dev/input") else {
+            return;
+        };
+
+        for entry in entries.flatten() {
+            if !entry.file_name().as_bytes().starts_with(b"event") {
+                continue;
+            }
+            let path = entry.path();
+            if self
+                .gamepads
+                .iter()
+                .any(|gamepad| gamepad.file.is_some() && gamepad.path == path)
+            {
+                continue;
+            }
+            let Some(free_slot) = self.gamepads.iter().position(|g| g.file.is_none()) else {
+                return;
+            };
+            if let Some(gamepad) = linux_open_gamepad(path) {
+                println!("Gamepad connected: {}", gamepad.path.display());
+                self.gamepads[free_slot] = gamepad;
+            }
+        }
+    }
+
+    fn linux_process_gamepads(&mut self) {
+        let now = Instant::now();
+        if self
+            .last_gamepad_scan
+            .is_none_or(|last_scan| now.duration_since(last_scan) >= GAMEPAD_RESCAN_INTERVAL)
+        {
+            self.last_gamepad_scan = Some(now);
+            self.linux_scan_for_gamepads();
+        }
+
+        for (gamepad_index, gamepad) in self.gamepads.iter_mut().enumerate() {
+            let controller_storage_index = gamepad_index + 1;
+            let old_controller = &mut self.old_input.controllers[controller_storage_index];
+            let new_controller = &mut self.new_input.controllers[controller_storage_index];
+
+            let Some(file) = &mut gamepad.file else {
+                // NOTE(aalhendi): This controller is not available
+                new_controller.is_connected = false;
+                continue;
+            };
+            if let Err(e) = linux_read_gamepad_events(file, &mut gamepad.state) {
+                println!("Gamepad disconnected: {} ({e})", gamepad.path.display());
+                *gamepad = LinuxGamepad::default();
+                new_controller.is_connected = false;
+                continue;
+            }
+
+            new_controller.is_connected = true;
+            new_controller.is_analog = old_controller.is_analog;
+
+            let pad = &gamepad.state;
+
+            let stick_left_x = linux_process_stick_value(
+                pad.left_stick_x,
+                pad.left_stick_x_range,
+                GAMEPAD_LEFT_THUMB_DEADZONE,
+            );
+            new_controller.left_stick_average_x = stick_left_x;
+
+            // NOTE(aalhendi): evdev Y grows downwards, XInput (and the game) want up to be positive.
+            let stick_left_y = -linux_process_stick_value(
+                pad.left_stick_y,
+                pad.left_stick_y_range,
+                GAMEPAD_LEFT_THUMB_DEADZONE,
+            );
+            new_controller.left_stick_average_y = stick_left_y;
+
+            if stick_left_x != 0.0 || stick_left_y != 0.0 {
+                new_controller.is_analog = true;
+            }
+
+            new_controller.right_stick_average_x = linux_process_stick_value(
+                pad.right_stick_x,
+                pad.right_stick_x_range,
+                GAMEPAD_RIGHT_THUMB_DEADZONE,
+            );
+            new_controller.right_stick_average_y = -linux_process_stick_value(
+                pad.right_stick_y,
+                pad.right_stick_y_range,
+                GAMEPAD_RIGHT_THUMB_DEADZONE,
+            );
+
+            let buttons = pad.linux_dpad_buttons();
+            if (buttons & LINUX_GAMEPAD_DPAD_UP) != 0 {
+                new_controller.is_analog = false;
+                new_controller.left_stick_average_y = 1.0;
+            }
+            if (buttons & LINUX_GAMEPAD_DPAD_DOWN) != 0 {
+                new_controller.is_analog = false;
+                new_controller.left_stick_average_y = -1.0;
+            }
+            if (buttons & LINUX_GAMEPAD_DPAD_LEFT) != 0 {
+                new_controller.is_analog = false;
+                new_controller.left_stick_average_x = -1.0;
+            }
+            if (buttons & LINUX_GAMEPAD_DPAD_RIGHT) != 0 {
+                new_controller.is_analog = false;
+                new_controller.left_stick_average_x = 1.0;
+            }
+
+            let threshold = 0.5_f32;
+            for button in GameButton::ALL {
+                // NOTE(aalhendi): fake dpad emulation from left stick
+                let is_stick_down = match button {
+                    GameButton::MoveLeft => new_controller.left_stick_average_x < -threshold,
+                    GameButton::MoveRight => new_controller.left_stick_average_x > threshold,
+                    GameButton::MoveUp => new_controller.left_stick_average_y > threshold,
+                    GameButton::MoveDown => new_controller.left_stick_average_y < -threshold,
+                    _ => false,
+                };
+                let is_down = is_stick_down
+                    || self
+                        .bindings
+                        .is_gamepad_button_down(button, |gamepad_button| {
+                            let button_bit = match gamepad_button {
+                                GamepadButton::A => LINUX_GAMEPAD_SOUTH,
+                                GamepadButton::B => LINUX_GAMEPAD_EAST,
+                                GamepadButton::X => LINUX_GAMEPAD_WEST,
+                                GamepadButton::Y => LINUX_GAMEPAD_NORTH,
+                                GamepadButton::LeftShoulder => LINUX_GAMEPAD_LEFT_SHOULDER,
+                                GamepadButton::RightShoulder => LINUX_GAMEPAD_RIGHT_SHOULDER,
+                                GamepadButton::Start => LINUX_GAMEPAD_START,
+                                GamepadButton::Back => LINUX_GAMEPAD_BACK,
+                            };
+                            (buttons & button_bit) != 0
+                        });
+                linux_process_gamepad_digital_button(
+                    is_down as LinuxGamepadButtonFlags,
+                    old_controller.button_mut(button),
+                    1,
+                    new_controller.button_mut(button),
+                );
+            }
+        }
+    }
+
+    /// Resets keyboard and mouse input for the upcoming frame, carrying over what is held.
+    /// winit delivers input events between frames, so this runs right after a frame is consumed.
+    fn linux_begin_input_frame(&mut self) {
+        let new_keyboard_controller = &mut self.new_input.controllers[0];
+        let old_keyboard_controller = &self.old_input.controllers[0];
+        // TODO(aalhendi): we can't zero everything because the up/down count will be wrong
+        *new_keyboard_controller = GameControllerInput::default();
+        new_keyboard_controller.is_connected = true;
+        for (i, button) in new_keyboard_controller.buttons.iter_mut().enumerate() {
+            button.ended_down = old_keyboard_controller.buttons[i].ended_down;
+        }
+
+        // NOTE(aalhendi): winit only tells us when the cursor moves, so the position carries over.
+        self.new_input.mouse_x = self.old_input.mouse_x;
+        self.new_input.mouse_y = self.old_input.mouse_y;
+        self.new_input.mouse_z = 0;
+        for (i, button) in self.new_input.mouse_buttons.iter_mut().enumerate() {
+            *button = GameButtonState {
+                half_transition_count: 0,
+                ended_down: self.old_input.mouse_buttons[i].ended_down,
+            };
+        }
+    }
+
+    fn linux_process_mouse_button_event(&mut self, button: MouseButton, state: ElementState) {
+        let button_index = match button {
+            MouseButton::Left => 0,
+            MouseButton::Middle => 1,
+            MouseButton::Right => 2,
+            MouseButton::Back => 3,
+            MouseButton::Forward => 4,
+            MouseButton::Other(_) => return,
+        };
+        linux_process_keyboard_message(
+            &mut self.new_input.mouse_buttons[button_index],
+            state == ElementState::Pressed,
+        );
+    }
+
+    fn linux_process_mouse_wheel_event(&mut self, delta: MouseScrollDelta) {
+        let notches = match delta {
+            MouseScrollDelta::LineDelta(_x, y) => y as f64,
+            MouseScrollDelta::PixelDelta(position) => position.y / PIXELS_PER_WHEEL_NOTCH,
+        };
+
+        // NOTE(aalhendi): touchpads and hi-res wheels report fractions of a notch,
+        //  keep the leftover around so slow scrolling still adds up.
+        self.mouse_wheel_remainder += notches;
+        let whole_notches = self.mouse_wheel_remainder.trunc();
+        self.mouse_wheel_remainder -= whole_notches;
+        self.new_input.mouse_z += whole_notches as i32;
+    }
+
+    fn linux_process_keyboard_event(&mut self, event_loop: &ActiveEventLoop, event: &KeyEvent) {
+        // NOTE(aalhendi): auto-repeat comes through as extra presses, we only care about transitions.
+        if event.repeat {
+            return;
+        }
+        let PhysicalKey::Code(key_code) = event.physical_key else {
+            return;
+        };
+
+        let is_down = event.state == ElementState::Pressed;
+        let bound_button = linux_binding_key(event).and_then(|key| self.bindings.key(key));
+        if let Some(button) = bound_button {
+            linux_process_keyboard_message(
+                self.new_input.controllers[0].button_mut(button),
+                is_down,
+            );
+        }
+        match key_code {
+            KeyCode::F4 if is_down && self.modifiers.alt_key() => {
+                println!("Alt + F4 pressed, quitting...");
+                event_loop.exit();
+            }
+            // NOTE(aalhendi): a bound key belongs to the game, the debug keys only get unbound ones.
+            _ if bound_button.is_some() => {}
+            #[cfg(feature = "internal_build")]
+            KeyCode::KeyP if is_down => {
+                self.is_paused = !self.is_paused;
+            }
+            #[cfg(feature = "internal_build")]
+            KeyCode::KeyL if is_down => {
+                // NOTE(aalhendi): record, then loop what was recorded, then stop.
+                let state = &mut self.state;
+                match (state.input_recording_idx, state.input_playing_idx) {
+                    (Some(replay_idx), _) | (None, Some(replay_idx)) => {
+                        state.linux_toggle_input_playback(replay_idx);
+                    }
+                    (None, None) => state.linux_toggle_input_recording(state.selected_replay_idx),
+                }
+            }
+            // NOTE(aalhendi): 1-4 loop that slot, Shift+1-4 record into it. Pressing the same again stops.
+            #[cfg(feature = "internal_build")]
+            KeyCode::Digit1 | KeyCode::Digit2 | KeyCode::Digit3 | KeyCode::Digit4 if is_down => {
+                let replay_idx = match key_code {
+                    KeyCode::Digit1 => 0,
+                    KeyCode::Digit2 => 1,
+                    KeyCode::Digit3 => 2,
+                    _ => 3,
+                };
+                let state = &mut self.state;
+                state.selected_replay_idx = replay_idx;
+                if self.modifiers.shift_key() {
+                    state.linux_toggle_input_recording(replay_idx);
+                } else {
+                    state.linux_toggle_input_playback(replay_idx);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn linux_reload_game_code(&mut self) {
+        let source_lib_name = ffi::CStr::from_bytes_until_nul(&self.source_lib_name)
+            .expect("Failed to create CStr from game library name");
+
+        // NOTE(aalhendi): We manually append the null terminator '\0' so CStr doesn't panic
+        let temp_str = format!("libhm_temp_{}.so\0", self.game_load_count);
+        let temp_c_str = ffi::CStr::from_bytes_with_nul(temp_str.as_bytes())
+            .expect("Failed to create CStr from temp file name");
+        let mut temp_lib_name = [0_u8; PATH_MAX_USIZE];
+        self.state
+            .linux_build_exe_path_file_name(temp_c_str, &mut temp_lib_name);
+        let temp_lib_name = ffi::CStr::from_bytes_until_nul(&temp_lib_name)
+            .expect("Failed to create CStr from temp library name");
+
+        // NOTE(aalhendi): the new code is loaded next to the old one, so if it turns out to be unusable
+        //  we can keep running the old code.
+        let game = linux_load_game_code(source_lib_name, temp_lib_name);
+        self.game_load_count = self.game_load_count.wrapping_add(1);
+        if game.is_valid || !self.game.is_valid {
+            self.game = game;
+        } else {
+            // NOTE(aalhendi): a half-written library comes back without a write time so it is retried
+            //  next frame, no need to say anything about those.
+            if game.last_write_time != LinuxFileTime::default() {
+                println!("Still running the previous game code");
+            }
+            self.game.last_write_time = game.last_write_time;
+        }
+    }
+
+    fn linux_update_and_render(&mut self) {
+        self.new_input.dt_for_frame = self.target_seconds_per_frame;
+
+        let source_lib_name = ffi::CStr::from_bytes_until_nul(&self.source_lib_name)
+            .expect("Failed to create CStr from game library name");
+        let new_lib_write_time = linux_get_last_write_time(source_lib_name);
+        if new_lib_write_time != self.game.last_write_time {
+            self.linux_reload_game_code();
+        }
+
+        self.linux_process_gamepads();
+
+        let mut thread_ctx = ThreadContext::default();
+
+        let mut buffer = GameOffscreenBuffer {
+            width: self.backbuffer.width,
+            height: self.backbuffer.height,
+            pitch: self.backbuffer.pitch,
+            bytes_per_pixel: self.backbuffer.bytes_per_pixel,
+            memory: self.backbuffer.memory,
+        };
+
+        if self.state.input_recording_idx.is_some() {
+            self.state.linux_record_input(&mut self.new_input);
+        }
+        if self.state.input_playing_idx.is_some() {
+            self.state.linux_playback_input(&mut self.new_input);
+        }
+
+        self.game_memory.frame_arena.reset();
+        if let Some(update_and_render) = self.game.update_and_render {
+            unsafe {
+                update_and_render(
+                    &mut thread_ctx,
+                    &mut self.game_memory,
+                    &mut self.new_input,
+                    &mut buffer,
+                );
+            }
+        }
+
+        let sample_count = self
+            .sound_output
+            .linux_get_sample_count(self.target_seconds_per_frame);
+        let mut sound_buffer = GameSoundOutputBuffer {
+            samples_per_second: self.sound_output.samples_per_second,
+            sample_count,
+            samples: self.sound_output.samples,
+        };
+
+        if let Some(get_sound_samples) = self.game.get_sound_samples {
+            unsafe {
+                get_sound_samples(&mut thread_ctx, &mut self.game_memory, &mut sound_buffer);
+            }
+        } else {
+            // NOTE(aalhendi): no game code (mid-reload), play silence instead of whatever was left over.
+            unsafe {
+                ptr::write_bytes(sound_buffer.samples, 0, sample_count as usize * 2);
+            }
+        }
+
+        self.sound_output.linux_fill_sound_buffer(&sound_buffer);
+
+        mem::swap(&mut self.new_input, &mut self.old_input);
+        self.linux_begin_input_frame();
+    }
+}
+
 impl ApplicationHandler for LinuxApp {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        if let LinuxAppState::Uninitialized = self.state {
+        if let LinuxAppWindow::Uninitialized = self.window {
             let window = Rc::new(
                 event_loop
-                    .create_window(WindowAttributes::default())
+                    .create_window(
+                        WindowAttributes::default()
+                            .with_title("Handmade Hero")
+                            .with_inner_size(PhysicalSize::new(
+                                self.window_size.0 as u32,
+                                self.window_size.1 as u32,
+                            )),
+                    )
                     .expect("Failed to create window"),
             );
 
@@ -39,11 +1578,21 @@ impl ApplicationHandler for LinuxApp {
             let surface = Surface::new(&context, Rc::clone(&window))
                 .expect("Failed to create softbuffer surface");
 
-            self.state = LinuxAppState::Running(LinuxState {
+            // NOTE(aalhendi): winit reports millihertz, or nothing at all for some X11/Wayland setups.
+            let monitor_refresh_hz = window
+                .current_monitor()
+                .and_then(|monitor| monitor.refresh_rate_millihertz())
+                .map(|millihertz| (millihertz + 500) / 1000)
+                .filter(|&hz| hz > 1)
+                .unwrap_or(60);
+            self.linux_set_monitor_refresh_hz(monitor_refresh_hz);
+
+            self.window = LinuxAppWindow::Running(LinuxWindow {
                 window,
                 _context: context,
                 surface,
             });
+            self.last_counter = linux_get_wall_clock();
         }
     }
 
@@ -66,14 +1615,28 @@ impl ApplicationHandler for LinuxApp {
             WindowEvent::HoveredFile(_) => {}
             WindowEvent::HoveredFileCancelled => {}
             WindowEvent::Focused(_) => {}
-            WindowEvent::KeyboardInput { .. } => {}
-            WindowEvent::ModifiersChanged(_) => {}
+            WindowEvent::KeyboardInput { event, .. } => {
+                self.linux_process_keyboard_event(event_loop, &event);
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
             WindowEvent::Ime(_) => {}
-            WindowEvent::CursorMoved { .. } => {}
+            WindowEvent::CursorMoved { position, .. } => {
+                let (mouse_x, mouse_y) = self
+                    .backbuffer
+                    .linux_window_to_backbuffer(position.x, position.y);
+                self.new_input.mouse_x = mouse_x;
+                self.new_input.mouse_y = mouse_y;
+            }
             WindowEvent::CursorEntered { .. } => {}
             WindowEvent::CursorLeft { .. } => {}
-            WindowEvent::MouseWheel { .. } => {}
-            WindowEvent::MouseInput { .. } => {}
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.linux_process_mouse_wheel_event(delta);
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.linux_process_mouse_button_event(button, state);
+            }
             WindowEvent::PinchGesture { .. } => {}
             WindowEvent::PanGesture { .. } => {}
             WindowEvent::DoubleTapGesture { .. } => {}
@@ -85,11 +1648,27 @@ impl ApplicationHandler for LinuxApp {
             WindowEvent::ThemeChanged(_) => {}
             WindowEvent::Occluded(_) => {}
             WindowEvent::RedrawRequested => {
-                let LinuxAppState::Running(state) = &mut self.state else {
+                if let LinuxAppWindow::Uninitialized = self.window {
+                    return;
+                }
+
+                if self.frames_left == Some(0) {
+                    event_loop.exit();
+                    return;
+                }
+                if !self.is_paused {
+                    self.linux_update_and_render();
+                    if let Some(frames_left) = &mut self.frames_left {
+                        *frames_left -= 1;
+                    }
+                }
+                self.linux_wait_for_frame_end();
+
+                let LinuxAppWindow::Running(window) = &mut self.window else {
                     return;
                 };
 
-                let size = state.window.inner_size();
+                let size = window.window.inner_size();
                 // NOTE(aalhendi): a minimized/transitioning Wayland window can report zero size.
                 // `softbuffer` requires non-zero dimensions, and there is nothing to present.
                 let Some(width) = NonZeroU32::new(size.width) else {
@@ -99,17 +1678,16 @@ impl ApplicationHandler for LinuxApp {
                     return;
                 };
 
-                state
+                window
                     .surface
                     .resize(width, height)
                     .expect("Failed to resize surface");
-                let mut buffer = state
+                let mut buffer = window
                     .surface
                     .buffer_mut()
                     .expect("Failed to get surface buffer");
-                for pixel in buffer.iter_mut() {
-                    *pixel = 0x00FF00FF;
-                }
+                self.backbuffer
+                    .linux_copy_buffer_to_window(&mut buffer, size.width, size.height);
 
                 buffer.present().expect("Failed to present surface buffer");
             }
@@ -117,20 +1695,443 @@ impl ApplicationHandler for LinuxApp {
     }
 
     fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
-        if let LinuxAppState::Running(state) = &self.state {
-            state.window.request_redraw();
+        if let LinuxAppWindow::Running(window) = &self.window {
+            window.window.request_redraw();
         }
     }
 }
 
-pub fn run() {
+pub fn run(options: &PlatformOptions) -> Result<(), String> {
+    let mut state = LinuxState::default();
+    state.linux_get_exe_file_name();
+
+    let mut source_lib_name = [0_u8; PATH_MAX_USIZE];
+    match &options.game_lib {
+        Some(game_lib) => {
+            let game_lib_bytes = game_lib.as_os_str().as_bytes();
+            // NOTE(aalhendi): leave the last byte zero for the null terminator.
+            if game_lib_bytes.len() >= PATH_MAX_USIZE || game_lib_bytes.contains(&0) {
+                return Err(format!(
+                    "{} is not a valid library path",
+                    game_lib.display()
+                ));
+            }
+            source_lib_name[..game_lib_bytes.len()].copy_from_slice(game_lib_bytes);
+        }
+        None => state.linux_build_exe_path_file_name(c"libhm.so", &mut source_lib_name),
+    }
+
+    let mut backbuffer = LinuxOffscreenBuffer {
+        memory: ptr::null_mut(),
+        width: 0,
+        height: 0,
+        pitch: 0,
+        bytes_per_pixel: 4,
+    };
+    let (backbuffer_width, backbuffer_height) = options.backbuffer_size;
+    backbuffer.linux_resize_backbuffer(backbuffer_width, backbuffer_height);
+
+    // NOTE(aalhendi): placeholder until the window exists and we can ask its monitor, see resumed().
+    let game_update_hz = 60_f32 / 2_f32;
+    let target_seconds_per_frame = 1_f64 / game_update_hz as f64;
+
+    // TODO(aalhendi): make this sixty seconds?
+    let sound_output = LinuxSoundOutput::new(48000, game_update_hz as u32);
+
+    let permanent_storage_size = options.permanent_storage_size;
+    let transient_storage_size = options.transient_storage_size;
+    let frame_arena_size = options.frame_arena_size;
+    let total_storage_size = permanent_storage_size + transient_storage_size + frame_arena_size;
+
+    state.total_size = total_storage_size;
+    // NOTE(aalhendi): internal builds want the same addresses every run so pointers stay stable across
+    //  runs and replays. If someone else already lives there, run anyway, just without that guarantee.
+    #[cfg(feature = "internal_build")]
+    {
+        use interface::terabytes_to_bytes;
+        let base_address = terabytes_to_bytes(2);
+        state.game_memory_block =
+            linux_allocate_memory_at(base_address, state.total_size).cast::<()>();
+        if state.game_memory_block.is_null() {
+            eprintln!(
+                "Failed to map game memory at {base_address:#x}, falling back to any address. \
+                 Pointers into game memory won't be stable across runs."
+            );
+        }
+    }
+    if state.game_memory_block.is_null() {
+        state.game_memory_block = linux_allocate_memory(state.total_size).cast::<()>();
+    }
+    if state.game_memory_block.is_null() {
+        panic!("Failed to allocate game memory");
+    }
+    let permanent_storage = state.game_memory_block;
+
+    state.linux_init_replay_buffers();
+
+    if let Some(replay) = &options.replay {
+        #[cfg(feature = "internal_build")]
+        {
+            // NOTE(aalhendi): --replay borrows slot 0, its snapshot becomes whatever the replay starts from.
+            if let Some(replay_state) = &options.replay_state {
+                let memory = unsafe {
+                    core::slice::from_raw_parts_mut(
+                        state.game_memory_block.cast::<u8>(),
+                        state.total_size,
+                    )
+                };
+                replay_read_snapshot(replay_state, memory)?;
+            }
+            state.linux_take_snapshot(0);
+            state.linux_begin_input_playback_from(0, replay.as_os_str())?;
+        }
+        #[cfg(not(feature = "internal_build"))]
+        return Err(format!(
+            "Can't play back {}, replays need an internal build",
+            replay.display()
+        ));
+    }
+
+    let (transient_storage, frame_arena) = split_transient_block(
+        permanent_storage
+            .cast::<u8>()
+            .wrapping_add(permanent_storage_size),
+        transient_storage_size,
+        frame_arena_size,
+    );
+    let game_memory = GameMemory {
+        is_initialized: false,
+        permanent_storage_size,
+        transient_storage_size,
+        permanent_storage,
+        transient_storage,
+        frame_arena,
+        platform: linux_platform_api(options.data_directory()?),
+    };
+
     let mut app = LinuxApp {
-        state: LinuxAppState::Uninitialized,
+        window: LinuxAppWindow::Uninitialized,
+        state,
+        backbuffer,
+        sound_output,
+        gamepads: Default::default(),
+        last_gamepad_scan: None,
+        game: LinuxGameCode::default(),
+        game_load_count: 0,
+        source_lib_name,
+        game_memory,
+        new_input: GameInput::default(),
+        old_input: GameInput::default(),
+        modifiers: ModifiersState::empty(),
+        mouse_wheel_remainder: 0.0,
+        // NOTE(aalhendi): only internal builds have a key to unpause.
+        is_paused: cfg!(feature = "internal_build") && options.start_paused,
+        frames_left: options.frame_count,
+        window_size: options.window_size.unwrap_or(options.backbuffer_size),
+        bindings: options.load_bindings()?,
+        game_update_hz,
+        target_seconds_per_frame,
+        last_counter: linux_get_wall_clock(),
     };
+    app.linux_reload_game_code();
+    app.linux_begin_input_frame();
+
     let event_loop = EventLoop::new().expect("Failed to create event loop");
     // NOTE(aalhendi): continuously run event loop, even if OS hasn't dispatched any events.
     event_loop.set_control_flow(ControlFlow::Poll);
     event_loop
         .run_app(&mut app)
-        .expect("Failed to run event loop");
+        .map_err(|e| format!("Failed to run event loop: {e}"))
+}
+
+// NOTE(aalhendi): the bits of linux/input.h and linux/input-event-codes.h we need. libc doesn't have them.
+mod evdev {
+    pub const EV_KEY: u16 = 0x01;
+    pub const EV_ABS: u16 = 0x03;
+
+    pub const KEY_MAX: usize = 0x2ff;
+
+    pub const BTN_SOUTH: u16 = 0x130;
+    pub const BTN_EAST: u16 = 0x131;
+    pub const BTN_NORTH: u16 = 0x133;
+    pub const BTN_WEST: u16 = 0x134;
+    pub const BTN_TL: u16 = 0x136;
+    pub const BTN_TR: u16 = 0x137;
+    pub const BTN_SELECT: u16 = 0x13a;
+    pub const BTN_START: u16 = 0x13b;
+    pub const BTN_DPAD_UP: u16 = 0x220;
+    pub const BTN_DPAD_DOWN: u16 = 0x221;
+    pub const BTN_DPAD_LEFT: u16 = 0x222;
+    pub const BTN_DPAD_RIGHT: u16 = 0x223;
+
+    pub const ABS_X: u16 = 0x00;
+    pub const ABS_Y: u16 = 0x01;
+    pub const ABS_RX: u16 = 0x03;
+    pub const ABS_RY: u16 = 0x04;
+    pub const ABS_HAT0X: u16 = 0x10;
+    pub const ABS_HAT0Y: u16 = 0x11;
+
+    const IOC_READ: libc::Ioctl = 2;
+
+    #[inline(always)]
+    const fn ioc_read(nr: u16, size: usize) -> libc::Ioctl {
+        (IOC_READ << 30)
+            | ((size as libc::Ioctl) << 16)
+            | ((b'E' as libc::Ioctl) << 8)
+            | nr as libc::Ioctl
+    }
+
+    /// EVIOCGBIT(ev, len): which codes of event type `ev` the device can send.
+    #[inline(always)]
+    pub const fn eviocgbit(ev: u16, len: usize) -> libc::Ioctl {
+        ioc_read(0x20 + ev, len)
+    }
+
+    /// EVIOCGABS(abs): current value and range of an absolute axis.
+    #[inline(always)]
+    pub const fn eviocgabs(abs: u16) -> libc::Ioctl {
+        ioc_read(0x40 + abs, size_of::<libc::input_absinfo>())
+    }
+}
+
+// NOTE(aalhendi): the subset of libasound we need. It is dlopen'd like dsound.dll on win32 so machines
+// without ALSA still start, just without sound.
+#[allow(non_camel_case_types)]
+mod alsa {
+    use core::ffi::{c_char, c_int, c_long, c_uint, c_ulong, c_void};
+
+    #[repr(C)]
+    pub struct snd_pcm_t {
+        _private: [u8; 0],
+    }
+
+    pub type snd_pcm_sframes_t = c_long;
+    pub type snd_pcm_uframes_t = c_ulong;
+
+    pub const SND_PCM_STREAM_PLAYBACK: c_int = 0;
+    pub const SND_PCM_NONBLOCK: c_int = 1;
+    pub const SND_PCM_FORMAT_S16_LE: c_int = 2;
+    pub const SND_PCM_ACCESS_RW_INTERLEAVED: c_int = 3;
+
+    pub type SndPcmOpenFn = unsafe extern "C" fn(
+        pcm: *mut *mut snd_pcm_t,
+        name: *const c_char,
+        stream: c_int,
+        mode: c_int,
+    ) -> c_int;
+    pub type SndPcmSetParamsFn = unsafe extern "C" fn(
+        pcm: *mut snd_pcm_t,
+        format: c_int,
+        access: c_int,
+        channels: c_uint,
+        rate: c_uint,
+        soft_resample: c_int,
+        latency: c_uint,
+    ) -> c_int;
+    pub type SndPcmWriteiFn = unsafe extern "C" fn(
+        pcm: *mut snd_pcm_t,
+        buffer: *const c_void,
+        size: snd_pcm_uframes_t,
+    ) -> snd_pcm_sframes_t;
+    pub type SndPcmAvailUpdateFn = unsafe extern "C" fn(pcm: *mut snd_pcm_t) -> snd_pcm_sframes_t;
+    pub type SndPcmDelayFn =
+        unsafe extern "C" fn(pcm: *mut snd_pcm_t, delay: *mut snd_pcm_sframes_t) -> c_int;
+    pub type SndPcmRecoverFn =
+        unsafe extern "C" fn(pcm: *mut snd_pcm_t, err: c_int, silent: c_int) -> c_int;
+    pub type SndPcmCloseFn = unsafe extern "C" fn(pcm: *mut snd_pcm_t) -> c_int;
+    pub type SndStrerrorFn = unsafe extern "C" fn(errnum: c_int) -> *const c_char;
+
+    pub struct AlsaLib {
+        pub snd_pcm_open: SndPcmOpenFn,
+        pub snd_pcm_set_params: SndPcmSetParamsFn,
+        pub snd_pcm_writei: SndPcmWriteiFn,
+        pub snd_pcm_avail_update: SndPcmAvailUpdateFn,
+        pub snd_pcm_delay: SndPcmDelayFn,
+        pub snd_pcm_recover: SndPcmRecoverFn,
+        pub snd_pcm_close: SndPcmCloseFn,
+        pub snd_strerror: SndStrerrorFn,
+    }
+
+    impl AlsaLib {
+        /// # Safety
+        /// dlopens libasound and trusts that the symbols have the signatures above.
+        pub unsafe fn load() -> Result<Self, &'static str> {
+            unsafe {
+                let lib = libc::dlopen(
+                    c"libasound.so.2".as_ptr(),
+                    libc::RTLD_NOW | libc::RTLD_LOCAL,
+                );
+                if lib.is_null() {
+                    return Err("Failed to load libasound.so.2");
+                }
+
+                macro_rules! load_symbol {
+                    ($name:ident, $ty:ty) => {{
+                        let symbol_name = concat!(stringify!($name), "\0");
+                        let symbol = libc::dlsym(lib, symbol_name.as_ptr().cast::<c_char>());
+                        if symbol.is_null() {
+                            libc::dlclose(lib);
+                            return Err(concat!("Failed to find ", stringify!($name)));
+                        }
+                        core::mem::transmute::<*mut c_void, $ty>(symbol)
+                    }};
+                }
+
+                // NOTE(aalhendi): we never dlclose libasound, it lives as long as the process does.
+                Ok(Self {
+                    snd_pcm_open: load_symbol!(snd_pcm_open, SndPcmOpenFn),
+                    snd_pcm_set_params: load_symbol!(snd_pcm_set_params, SndPcmSetParamsFn),
+                    snd_pcm_writei: load_symbol!(snd_pcm_writei, SndPcmWriteiFn),
+                    snd_pcm_avail_update: load_symbol!(snd_pcm_avail_update, SndPcmAvailUpdateFn),
+                    snd_pcm_delay: load_symbol!(snd_pcm_delay, SndPcmDelayFn),
+                    snd_pcm_recover: load_symbol!(snd_pcm_recover, SndPcmRecoverFn),
+                    snd_pcm_close: load_symbol!(snd_pcm_close, SndPcmCloseFn),
+                    snd_strerror: load_symbol!(snd_strerror, SndStrerrorFn),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EV_SYN: u16 = 0x00;
+    const SYN_REPORT: u16 = 0x00;
+
+    fn event_bytes(events: &[(u16, u16, i32)]) -> Vec<u8> {
+        events
+            .iter()
+            .flat_map(|&(event_type, code, value)| {
+                let mut event = unsafe { mem::zeroed::<libc::input_event>() };
+                event.type_ = event_type;
+                event.code = code;
+                event.value = value;
+                let bytes = unsafe {
+                    core::slice::from_raw_parts(
+                        (&raw const event).cast::<u8>(),
+                        size_of::<libc::input_event>(),
+                    )
+                };
+                bytes.to_vec()
+            })
+            .collect()
+    }
+
+    fn read_events(state: &mut LinuxGamepadState, events: &[(u16, u16, i32)]) {
+        let bytes = event_bytes(events);
+        linux_read_gamepad_events(&mut bytes.as_slice(), state).unwrap();
+    }
+
+    #[test]
+    fn key_events_set_and_clear_button_flags() {
+        let mut state = LinuxGamepadState::default();
+        read_events(
+            &mut state,
+            &[
+                (evdev::EV_KEY, evdev::BTN_SOUTH, 1),
+                (evdev::EV_KEY, evdev::BTN_START, 1),
+                (evdev::EV_KEY, evdev::BTN_TL, 1),
+                (EV_SYN, SYN_REPORT, 0),
+            ],
+        );
+        assert_eq!(
+            state.buttons,
+            LINUX_GAMEPAD_SOUTH | LINUX_GAMEPAD_START | LINUX_GAMEPAD_LEFT_SHOULDER
+        );
+
+        // NOTE(aalhendi): 2 is autorepeat, the button stays down.
+        read_events(
+            &mut state,
+            &[
+                (evdev::EV_KEY, evdev::BTN_SOUTH, 2),
+                (evdev::EV_KEY, evdev::BTN_START, 0),
+                (EV_SYN, SYN_REPORT, 0),
+            ],
+        );
+        assert_eq!(
+            state.buttons,
+            LINUX_GAMEPAD_SOUTH | LINUX_GAMEPAD_LEFT_SHOULDER
+        );
+    }
+
+    #[test]
+    fn hat_events_become_dpad_buttons() {
+        let mut state = LinuxGamepadState::default();
+        read_events(
+            &mut state,
+            &[
+                (evdev::EV_ABS, evdev::ABS_HAT0X, 1),
+                (evdev::EV_ABS, evdev::ABS_HAT0Y, -1),
+                (EV_SYN, SYN_REPORT, 0),
+            ],
+        );
+        assert_eq!((state.hat_x, state.hat_y), (1, -1));
+        assert_eq!(
+            state.linux_dpad_buttons(),
+            LINUX_GAMEPAD_DPAD_RIGHT | LINUX_GAMEPAD_DPAD_UP
+        );
+
+        read_events(
+            &mut state,
+            &[
+                (evdev::EV_ABS, evdev::ABS_HAT0X, 0),
+                (evdev::EV_ABS, evdev::ABS_HAT0Y, 1),
+                (EV_SYN, SYN_REPORT, 0),
+            ],
+        );
+        assert_eq!(state.linux_dpad_buttons(), LINUX_GAMEPAD_DPAD_DOWN);
+    }
+
+    #[test]
+    fn stick_events_update_axes() {
+        let mut state = LinuxGamepadState::default();
+        read_events(
+            &mut state,
+            &[
+                (evdev::EV_ABS, evdev::ABS_X, 32767),
+                (evdev::EV_ABS, evdev::ABS_Y, -32768),
+                (evdev::EV_ABS, evdev::ABS_RX, 1000),
+                (evdev::EV_ABS, evdev::ABS_RY, -20000),
+                (EV_SYN, SYN_REPORT, 0),
+            ],
+        );
+        assert_eq!(
+            (
+                state.left_stick_x,
+                state.left_stick_y,
+                state.right_stick_x,
+                state.right_stick_y
+            ),
+            (32767, -32768, 1000, -20000)
+        );
+        assert_eq!(state.buttons, 0);
+
+        let range = LinuxAxisRange::default();
+        assert_eq!(
+            linux_process_stick_value(state.left_stick_x, range, GAMEPAD_LEFT_THUMB_DEADZONE),
+            1.0
+        );
+        assert_eq!(
+            linux_process_stick_value(state.left_stick_y, range, GAMEPAD_LEFT_THUMB_DEADZONE),
+            -1.0
+        );
+        assert_eq!(
+            linux_process_stick_value(state.right_stick_x, range, GAMEPAD_RIGHT_THUMB_DEADZONE),
+            0.0
+        );
+        assert!(
+            linux_process_stick_value(state.right_stick_y, range, GAMEPAD_RIGHT_THUMB_DEADZONE)
+                < 0.0
+        );
+    }
+
+    #[test]
+    fn reads_more_events_than_fit_in_one_read() {
+        let events: Vec<_> = (0..200).map(|i| (evdev::EV_ABS, evdev::ABS_X, i)).collect();
+        let mut state = LinuxGamepadState::default();
+        read_events(&mut state, &events);
+        assert_eq!(state.left_stick_x, 199);
+    }
 }
@@ -3,22 +3,32 @@
 #[cfg(not(target_os = "windows"))]
 compile_error!("win32_platform can only be built on Windows.");
 
-use core::{arch::x86_64, ffi, mem, ptr};
+use core::{
+    arch::x86_64,
+    cell::UnsafeCell,
+    ffi, mem, ptr,
+    sync::atomic::{AtomicU32, Ordering},
+};
 use interface::{
-    GameButton, GameButtonState, GameControllerInput, GameGetSoundSamplesFn, GameInput, GameMemory,
-    GameOffscreenBuffer, GameSoundOutputBuffer, GameUpdateAndRenderFn, ThreadContext,
-    gigabytes_to_bytes, megabytes_to_bytes,
+    GameButton, GameButtonState, GameControllerInput, GameGetApiHashFn, GameGetSoundSamplesFn,
+    GameInput, GameMemory, GameOffscreenBuffer, GameSoundOutputBuffer, GameUpdateAndRenderFn,
+    GamepadButton, InputBindings, InputKey, PLATFORM_API_VERSION, PlatformApi, PlatformFileGroup,
+    PlatformFileHandle, PlatformFileSystem, PlatformFileType, PlatformOptions,
+    PlatformReadFileResult, PlatformWorkQueue, PlatformWorkQueueCallback, REPLAY_EMPTY_MEMORY_HASH,
+    REPLAY_PAGE_SIZE, ReplayHeader, ReplaySnapshotHeader, ThreadContext, game_api_check,
+    replay_hash_page, replay_page_is_zero, split_transient_block,
 };
+use std::{ffi::CString, path::PathBuf};
 
 #[cfg(feature = "internal_build")]
-use interface::DebugPlatformReadFileResult;
+use interface::replay_read_snapshot;
 
 use windows_sys::{
     Win32::{
         Foundation::{
             CloseHandle, ERROR_SUCCESS, FALSE, FARPROC, FILETIME, FreeLibrary, GENERIC_READ,
-            GENERIC_WRITE, HANDLE, HINSTANCE, HMODULE, HWND, INVALID_HANDLE_VALUE, LPARAM, LRESULT,
-            MAX_PATH, POINT, RECT, TRUE, WPARAM,
+            GENERIC_WRITE, GetLastError, HANDLE, HINSTANCE, HMODULE, HWND, INVALID_HANDLE_VALUE,
+            LPARAM, LRESULT, MAX_PATH, POINT, RECT, TRUE, WPARAM,
         },
         Graphics::Gdi::{
             BI_RGB, BITMAPINFO, BITMAPINFOHEADER, BeginPaint, DIB_RGB_COLORS, EndPaint, GetDC,
@@ -30,24 +40,33 @@ use windows_sys::{
             TIMERR_NOERROR, timeBeginPeriod,
         },
         Storage::FileSystem::{
-            CREATE_ALWAYS, CompareFileTime, CopyFileA, CreateFileA, FILE_ATTRIBUTE_NORMAL,
-            FILE_SHARE_NONE, FILE_SHARE_READ, GetFileAttributesExA, GetFileExInfoStandard,
-            OPEN_EXISTING, ReadFile, WIN32_FILE_ATTRIBUTE_DATA, WriteFile,
+            CREATE_ALWAYS, CompareFileTime, CopyFileA, CreateFileA, FILE_ATTRIBUTE_DIRECTORY,
+            FILE_ATTRIBUTE_NORMAL, FILE_BEGIN, FILE_CURRENT, FILE_SHARE_NONE, FILE_SHARE_READ,
+            FindClose, FindFirstFileA, FindNextFileA, GetFileAttributesExA, GetFileExInfoStandard,
+            GetFileSizeEx, OPEN_EXISTING, ReadFile, SetFilePointerEx, WIN32_FILE_ATTRIBUTE_DATA,
+            WIN32_FIND_DATAA, WriteFile,
         },
         System::{
+            IO::{OVERLAPPED, OVERLAPPED_0, OVERLAPPED_0_0},
             LibraryLoader::{GetModuleFileNameA, GetModuleHandleA, GetProcAddress, LoadLibraryA},
             Memory::{
-                CreateFileMappingA, FILE_MAP_ALL_ACCESS, MEM_COMMIT, MEM_RELEASE, MEM_RESERVE,
-                MapViewOfFile, PAGE_READWRITE, VirtualAlloc, VirtualFree,
+                GetWriteWatch, MEM_COMMIT, MEM_DECOMMIT, MEM_RELEASE, MEM_RESERVE, PAGE_READWRITE,
+                VirtualAlloc, VirtualFree,
             },
             Performance::{QueryPerformanceCounter, QueryPerformanceFrequency},
+            SystemServices::MEM_WRITE_WATCH,
+            Threading::{
+                CreateSemaphoreExA, CreateThread, INFINITE, ReleaseSemaphore, SEMAPHORE_ALL_ACCESS,
+                WaitForSingleObjectEx,
+            },
         },
         UI::{
             Input::{
                 KeyboardAndMouse::{
-                    GetKeyState, VIRTUAL_KEY, VK_A, VK_D, VK_DOWN, VK_E, VK_ESCAPE, VK_F4, VK_L,
-                    VK_LBUTTON, VK_LEFT, VK_MBUTTON, VK_P, VK_Q, VK_RBUTTON, VK_RIGHT, VK_S,
-                    VK_SPACE, VK_UP, VK_W, VK_XBUTTON1, VK_XBUTTON2,
+                    GetKeyState, VIRTUAL_KEY, VK_0, VK_1, VK_2, VK_3, VK_4, VK_9, VK_A, VK_BACK,
+                    VK_CONTROL, VK_DOWN, VK_ESCAPE, VK_F1, VK_F4, VK_F12, VK_L, VK_LBUTTON,
+                    VK_LEFT, VK_MBUTTON, VK_MENU, VK_P, VK_RBUTTON, VK_RETURN, VK_RIGHT, VK_SHIFT,
+                    VK_SPACE, VK_TAB, VK_UP, VK_XBUTTON1, VK_XBUTTON2, VK_Z,
                 },
                 XboxController::{
                     XINPUT_GAMEPAD_A, XINPUT_GAMEPAD_B, XINPUT_GAMEPAD_BACK,
@@ -69,7 +88,6 @@ use windows_sys::{
 - Saved game locations
 - Getting a handle to our own executable file
 - Asset loading path
-- Threading (launch a thread)
 - Raw Input (support for multiple keyboards)
 - Sleep/timeBeginPeriod
 - ClipCursor() (for multimonitor support)
@@ -126,12 +144,25 @@ struct Win32State {
     total_size: usize,
     game_memory_block: *mut (),
     replay_buffers: [Win32ReplayBuffer; 4],
+    // NOTE(aalhendi): room for GetWriteWatch to list every page of game memory.
+    touched_pages: *mut *mut ffi::c_void,
 
     recording_file_handle: HANDLE,
-    input_playing_idx: u32,
+    // NOTE(aalhendi): written as a placeholder when recording starts, rewritten with the final frame
+    //  count and dt when it ends.
+    recording_header: ReplayHeader,
+    input_playing_idx: Option<u32>,
 
-    input_recording_idx: u32,
+    input_recording_idx: Option<u32>,
     playback_file_handle: HANDLE,
+    playback_data_offset: u64,
+    // NOTE(aalhendi): the slot L records into, the last one picked with the number keys.
+    #[cfg(feature = "internal_build")]
+    selected_replay_idx: u32,
+
+    // NOTE(aalhendi): hi-res wheels send less than WHEEL_DELTA at a time, this holds the leftover.
+    mouse_wheel_remainder: i32,
+    bindings: InputBindings,
 
     pub exe_file_name: [u8; MAX_PATH_USIZE],
     pub exe_file_name_base_offset: usize,
@@ -143,15 +174,23 @@ impl Default for Win32State {
             total_size: 0,
             game_memory_block: ptr::null_mut(),
             replay_buffers: [Win32ReplayBuffer {
-                file_handle: INVALID_HANDLE_VALUE,
-                memory_map: INVALID_HANDLE_VALUE,
                 file_name: [0; MAX_PATH_USIZE],
                 memory_block: ptr::null_mut(),
+                page_indices: ptr::null_mut(),
+                page_count: 0,
+                memory_hash: REPLAY_EMPTY_MEMORY_HASH,
             }; 4],
+            touched_pages: ptr::null_mut(),
             recording_file_handle: INVALID_HANDLE_VALUE,
-            input_playing_idx: 0,
-            input_recording_idx: 0,
+            recording_header: ReplayHeader::new(0.0, 0),
+            input_playing_idx: None,
+            input_recording_idx: None,
             playback_file_handle: INVALID_HANDLE_VALUE,
+            playback_data_offset: 0,
+            #[cfg(feature = "internal_build")]
+            selected_replay_idx: 0,
+            mouse_wheel_remainder: 0,
+            bindings: InputBindings::default(),
             exe_file_name: [0; MAX_PATH_USIZE],
             exe_file_name_base_offset: 0,
         }
@@ -205,6 +244,121 @@ impl Win32State {
         dest[base_len..end_idx].copy_from_slice(file_bytes);
     }
 
+    /// Lists the pages of game memory written since it was allocated in `touched_pages`, returning
+    /// how many there are. None if that can't be known, then every page has to be treated as touched.
+    fn win32_get_touched_pages(&self) -> Option<usize> {
+        if self.touched_pages.is_null() {
+            return None;
+        }
+
+        // NOTE(aalhendi): the pages come back in address order, which is the order snapshots hash in.
+        let mut touched_page_count = self.total_size.div_ceil(REPLAY_PAGE_SIZE);
+        let mut granularity = 0_u32;
+        let result = unsafe {
+            GetWriteWatch(
+                0,
+                self.game_memory_block as *const ffi::c_void,
+                self.total_size,
+                self.touched_pages,
+                &mut touched_page_count,
+                &mut granularity,
+            )
+        };
+        if result != 0 {
+            eprintln!("GetWriteWatch failed, snapshots will scan all of memory");
+            return None;
+        }
+        debug_assert!(granularity as usize == REPLAY_PAGE_SIZE);
+        Some(touched_page_count)
+    }
+
+    /// Copies the non-zero pages of game memory into a slot's replay buffer. Pages the game never
+    /// wrote to are zero and aren't even looked at.
+    fn win32_take_snapshot(&mut self, replay_idx: usize) {
+        let touched_page_count = self.win32_get_touched_pages();
+        let page_count = touched_page_count.unwrap_or(self.total_size.div_ceil(REPLAY_PAGE_SIZE));
+        let replay_buffer = &mut self.replay_buffers[replay_idx];
+
+        // NOTE(aalhendi): drop the previous snapshot, only pages we copy into get committed again.
+        unsafe {
+            VirtualFree(
+                replay_buffer.memory_block.cast::<ffi::c_void>(),
+                self.total_size,
+                MEM_DECOMMIT,
+            );
+        }
+        replay_buffer.page_count = 0;
+        replay_buffer.memory_hash = REPLAY_EMPTY_MEMORY_HASH;
+
+        for touched_page in 0..page_count {
+            let offset = match touched_page_count {
+                Some(_) => unsafe {
+                    *self.touched_pages.add(touched_page) as usize - self.game_memory_block as usize
+                },
+                None => touched_page * REPLAY_PAGE_SIZE,
+            };
+            let page = unsafe {
+                core::slice::from_raw_parts(
+                    self.game_memory_block.cast::<u8>().add(offset),
+                    REPLAY_PAGE_SIZE.min(self.total_size - offset),
+                )
+            };
+            if replay_page_is_zero(page) {
+                continue;
+            }
+
+            let page_index = (offset / REPLAY_PAGE_SIZE) as u32;
+            unsafe {
+                let replay_page = VirtualAlloc(
+                    replay_buffer.memory_block.cast::<u8>().add(offset) as *const ffi::c_void,
+                    page.len(),
+                    MEM_COMMIT,
+                    PAGE_READWRITE,
+                );
+                if replay_page.is_null() {
+                    panic!("Failed to commit replay buffer page");
+                }
+                ptr::copy_nonoverlapping(page.as_ptr(), replay_page.cast::<u8>(), page.len());
+                *replay_buffer
+                    .page_indices
+                    .add(replay_buffer.page_count as usize) = page_index;
+            }
+            replay_buffer.page_count += 1;
+            replay_buffer.memory_hash =
+                replay_hash_page(replay_buffer.memory_hash, page_index, page);
+        }
+    }
+
+    /// Zeroes the pages the game has written to and copies a slot's snapshot pages back over them.
+    fn win32_restore_snapshot(&self, replay_idx: usize) {
+        let replay_buffer = self.replay_buffers[replay_idx];
+        unsafe {
+            match self.win32_get_touched_pages() {
+                Some(touched_page_count) => {
+                    for touched_page in 0..touched_page_count {
+                        let offset = *self.touched_pages.add(touched_page) as usize
+                            - self.game_memory_block as usize;
+                        ptr::write_bytes(
+                            self.game_memory_block.cast::<u8>().add(offset),
+                            0,
+                            REPLAY_PAGE_SIZE.min(self.total_size - offset),
+                        );
+                    }
+                }
+                None => ptr::write_bytes(self.game_memory_block.cast::<u8>(), 0, self.total_size),
+            }
+
+            for page in 0..replay_buffer.page_count as usize {
+                let offset = *replay_buffer.page_indices.add(page) as usize * REPLAY_PAGE_SIZE;
+                ptr::copy_nonoverlapping(
+                    replay_buffer.memory_block.cast::<u8>().add(offset),
+                    self.game_memory_block.cast::<u8>().add(offset),
+                    REPLAY_PAGE_SIZE.min(self.total_size - offset),
+                );
+            }
+        }
+    }
+
     fn win32_begin_recording_input(&mut self, input_recording_idx: u32) {
         // TODO(aalhendi): These files must fo in a temp/build directory!
         debug_assert!(input_recording_idx < self.replay_buffers.len() as u32);
@@ -215,7 +369,7 @@ impl Win32State {
             return;
         }
 
-        self.input_recording_idx = input_recording_idx;
+        self.input_recording_idx = Some(input_recording_idx);
 
         let mut file_name = [0_u8; MAX_PATH_USIZE];
         self.win32_get_input_file_location(true, input_recording_idx, &mut file_name);
@@ -236,47 +390,57 @@ impl Win32State {
         }
 
         // NOTE(aalhendi): take memory snapshot (Copy from Game -> Replay Buffer)
-        unsafe {
-            ptr::copy_nonoverlapping(
-                self.game_memory_block.cast::<u8>(),
-                replay_buffer.memory_block.cast::<u8>(),
-                self.total_size,
-            );
+        self.win32_take_snapshot(input_recording_idx as usize);
+        let replay_buffer = &self.replay_buffers[input_recording_idx as usize];
+        if !win32_write_snapshot_file(replay_buffer, self.total_size) {
+            // NOTE(aalhendi): the snapshot in memory is all playback needs, the file is for tools.
+            eprintln!("Failed to write replay snapshot");
         }
+
+        let memory_hash = replay_buffer.memory_hash;
+        self.recording_header = ReplayHeader::new(0.0, memory_hash);
+        if !win32_write_replay_header(self.recording_file_handle, &self.recording_header) {
+            eprintln!("Failed to write recording header");
+        }
+        println!("Recording into replay slot {}", input_recording_idx + 1);
     }
 
     fn win32_end_recording_input(&mut self) {
+        if !win32_write_replay_header(self.recording_file_handle, &self.recording_header) {
+            eprintln!("Failed to finish recording header");
+        }
         unsafe {
             CloseHandle(self.recording_file_handle);
         }
-        self.input_recording_idx = 0;
+        self.input_recording_idx = None;
     }
 
     fn win32_begin_input_playback(&mut self, input_playing_idx: u32) {
-        debug_assert!(input_playing_idx < self.replay_buffers.len() as u32);
-        let replay_buffer = &mut self.replay_buffers[input_playing_idx as usize];
-        if replay_buffer.memory_block.is_null() {
-            println!("No replay buffer found!");
-            return;
-        }
-
-        self.input_playing_idx = input_playing_idx;
+        let mut file_name = [0_u8; MAX_PATH_USIZE];
+        self.win32_get_input_file_location(true, input_playing_idx, &mut file_name);
+        let file_name = ffi::CStr::from_bytes_until_nul(&file_name)
+            .expect("Failed to create CStr from input file name");
 
-        // NOTE(aalhendi): restore the memory snapshot (Copy from Replay Buffer -> Game)
-        unsafe {
-            ptr::copy_nonoverlapping(
-                replay_buffer.memory_block.cast::<u8>(),
-                self.game_memory_block.cast::<u8>(),
-                self.total_size,
-            );
+        if let Err(e) = self.win32_begin_input_playback_from(input_playing_idx, file_name) {
+            eprintln!("{e}");
         }
+    }
 
-        let mut file_name = [0_u8; MAX_PATH_USIZE];
-        self.win32_get_input_file_location(true, input_playing_idx, &mut file_name);
+    /// Loops the input stream in `file_name`, starting from the snapshot held in `input_playing_idx`.
+    fn win32_begin_input_playback_from(
+        &mut self,
+        input_playing_idx: u32,
+        file_name: &ffi::CStr,
+    ) -> Result<(), String> {
+        debug_assert!(input_playing_idx < self.replay_buffers.len() as u32);
+        let replay_buffer = self.replay_buffers[input_playing_idx as usize];
+        if replay_buffer.memory_block.is_null() {
+            return Err("No replay buffer found!".to_string());
+        }
 
-        self.playback_file_handle = unsafe {
+        let playback_file_handle = unsafe {
             CreateFileA(
-                file_name.as_ptr(),
+                file_name.as_ptr().cast::<u8>(),
                 GENERIC_READ,
                 FILE_SHARE_READ,
                 ptr::null(),
@@ -285,13 +449,71 @@ impl Win32State {
                 0 as HANDLE,
             )
         };
+        if playback_file_handle == INVALID_HANDLE_VALUE {
+            return Err(format!("Failed to open playback file {file_name:?}"));
+        }
+
+        let header = win32_read_replay_header(playback_file_handle).and_then(|header| {
+            if header.memory_hash != 0 && replay_buffer.memory_hash != header.memory_hash {
+                Err("it was recorded from a different memory snapshot".to_string())
+            } else {
+                Ok(header)
+            }
+        });
+        let header = match header {
+            Ok(header) => header,
+            Err(e) => {
+                unsafe {
+                    CloseHandle(playback_file_handle);
+                }
+                return Err(format!("Refusing to play back {file_name:?}: {e}"));
+            }
+        };
+
+        self.playback_file_handle = playback_file_handle;
+        self.playback_data_offset = header.data_offset();
+        self.input_playing_idx = Some(input_playing_idx);
+        println!("Looping replay slot {}", input_playing_idx + 1);
+
+        // NOTE(aalhendi): restore the memory snapshot (Copy from Replay Buffer -> Game)
+        self.win32_restore_snapshot(input_playing_idx as usize);
+        Ok(())
     }
 
     fn win32_end_input_playback(&mut self) {
         unsafe {
             CloseHandle(self.playback_file_handle);
         }
-        self.input_playing_idx = 0;
+        self.input_playing_idx = None;
+    }
+
+    /// Stops whatever is recording or playing and loops `replay_idx`, or stops it if it is already looping.
+    fn win32_toggle_input_playback(&mut self, replay_idx: u32) {
+        let was_playing = self.input_playing_idx == Some(replay_idx);
+        if self.input_recording_idx.is_some() {
+            self.win32_end_recording_input();
+        }
+        if self.input_playing_idx.is_some() {
+            self.win32_end_input_playback();
+        }
+        if !was_playing {
+            self.win32_begin_input_playback(replay_idx);
+        }
+    }
+
+    /// Stops whatever is recording or playing and records into `replay_idx`, or stops it if it is
+    /// already recording.
+    fn win32_toggle_input_recording(&mut self, replay_idx: u32) {
+        let was_recording = self.input_recording_idx == Some(replay_idx);
+        if self.input_recording_idx.is_some() {
+            self.win32_end_recording_input();
+        }
+        if self.input_playing_idx.is_some() {
+            self.win32_end_input_playback();
+        }
+        if !was_recording {
+            self.win32_begin_recording_input(replay_idx);
+        }
     }
 
     fn win32_record_input(&mut self, new_input: &mut GameInput) {
@@ -308,7 +530,13 @@ impl Win32State {
         };
         if write_result == FALSE || bytes_written != memory_size {
             eprintln!("Failed to write into recording file: {write_result:?}");
+            return;
+        }
+
+        if self.recording_header.frame_count == 0 {
+            self.recording_header.dt_for_frame = new_input.dt_for_frame;
         }
+        self.recording_header.frame_count += 1;
     }
 
     fn win32_playback_input(&mut self, new_input: &mut GameInput) {
@@ -324,11 +552,18 @@ impl Win32State {
             )
         };
         if read_result == TRUE && bytes_read == 0 {
-            // NOTE(aalhendi): we've hit the end of the stream, go back to the beginning
-            let playing_idx = self.input_playing_idx;
-            self.win32_end_input_playback();
-            self.win32_begin_input_playback(playing_idx);
+            // NOTE(aalhendi): we've hit the end of the stream, go back to the beginning. The
+            //  snapshot was checked against the header when playback began, no need to again.
+            if let Some(input_playing_idx) = self.input_playing_idx {
+                self.win32_restore_snapshot(input_playing_idx as usize);
+            }
             unsafe {
+                SetFilePointerEx(
+                    self.playback_file_handle,
+                    self.playback_data_offset as i64,
+                    ptr::null_mut(),
+                    FILE_BEGIN,
+                );
                 ReadFile(
                     self.playback_file_handle,
                     (new_input as *mut GameInput).cast::<u8>(),
@@ -341,12 +576,142 @@ impl Win32State {
     }
 }
 
+/// Writes `header` over the start of the file, then moves back to where the file pointer was.
+fn win32_write_replay_header(file_handle: HANDLE, header: &ReplayHeader) -> bool {
+    let header_bytes = header.to_bytes();
+    let mut position = 0_i64;
+    let mut bytes_written = 0_u32;
+    unsafe {
+        SetFilePointerEx(file_handle, 0, &mut position, FILE_CURRENT) != FALSE
+            && SetFilePointerEx(file_handle, 0, ptr::null_mut(), FILE_BEGIN) != FALSE
+            && WriteFile(
+                file_handle,
+                header_bytes.as_ptr(),
+                header_bytes.len() as u32,
+                &mut bytes_written,
+                ptr::null_mut(),
+            ) != FALSE
+            && bytes_written == header_bytes.len() as u32
+            && SetFilePointerEx(
+                file_handle,
+                position.max(bytes_written as i64),
+                ptr::null_mut(),
+                FILE_BEGIN,
+            ) != FALSE
+    }
+}
+
+/// Reads and validates the header of a replay input stream, leaving the file at the first frame.
+fn win32_read_replay_header(file_handle: HANDLE) -> Result<ReplayHeader, String> {
+    let mut stream_size = 0_i64;
+    if unsafe { GetFileSizeEx(file_handle, &mut stream_size) } == FALSE {
+        return Err("failed to get the file size".to_string());
+    }
+
+    let mut prefix = [0_u8; ReplayHeader::SIZE];
+    let mut bytes_read = 0_u32;
+    let read_result = unsafe {
+        ReadFile(
+            file_handle,
+            prefix.as_mut_ptr(),
+            prefix.len() as u32,
+            &mut bytes_read,
+            ptr::null_mut(),
+        )
+    };
+    if read_result == FALSE {
+        return Err("failed to read the header".to_string());
+    }
+
+    let header = ReplayHeader::parse(&prefix[..bytes_read as usize], stream_size as u64)
+        .map_err(|e| e.to_string())?;
+    if unsafe {
+        SetFilePointerEx(
+            file_handle,
+            header.data_offset() as i64,
+            ptr::null_mut(),
+            FILE_BEGIN,
+        )
+    } == FALSE
+    {
+        return Err("failed to seek to the first frame".to_string());
+    }
+    Ok(header)
+}
+
+/// Writes a `ReplaySnapshotHeader`, the page index list and then the pages themselves.
+fn win32_write_snapshot_file(replay_buffer: &Win32ReplayBuffer, total_size: usize) -> bool {
+    let file_handle = unsafe {
+        CreateFileA(
+            replay_buffer.file_name.as_ptr(),
+            GENERIC_WRITE,
+            FILE_SHARE_NONE,
+            ptr::null(),
+            CREATE_ALWAYS,
+            FILE_ATTRIBUTE_NORMAL,
+            ptr::null_mut(),
+        )
+    };
+    if file_handle == INVALID_HANDLE_VALUE {
+        return false;
+    }
+
+    let write_all = |bytes: &[u8]| {
+        let mut bytes_written = 0_u32;
+        unsafe {
+            WriteFile(
+                file_handle,
+                bytes.as_ptr(),
+                bytes.len() as u32,
+                &mut bytes_written,
+                ptr::null_mut(),
+            ) != FALSE
+                && bytes_written == bytes.len() as u32
+        }
+    };
+
+    let header = ReplaySnapshotHeader::new(
+        total_size as u64,
+        replay_buffer.page_count,
+        replay_buffer.memory_hash,
+    );
+    // NOTE(aalhendi): x86_64 is little endian, the page indices can go out as they are in memory.
+    let page_indices = unsafe {
+        core::slice::from_raw_parts(
+            replay_buffer.page_indices.cast::<u8>(),
+            replay_buffer.page_count as usize * size_of::<u32>(),
+        )
+    };
+    let mut result = write_all(&header.to_bytes()) && write_all(page_indices);
+    for page in 0..replay_buffer.page_count as usize {
+        if !result {
+            break;
+        }
+        let offset = unsafe { *replay_buffer.page_indices.add(page) } as usize * REPLAY_PAGE_SIZE;
+        result = write_all(unsafe {
+            core::slice::from_raw_parts(
+                replay_buffer.memory_block.cast::<u8>().add(offset),
+                REPLAY_PAGE_SIZE.min(total_size - offset),
+            )
+        });
+    }
+
+    unsafe {
+        CloseHandle(file_handle);
+    }
+    result
+}
+
 #[derive(Clone, Copy)]
 struct Win32ReplayBuffer {
-    pub file_handle: HANDLE,
-    pub memory_map: HANDLE,
     pub file_name: [u8; MAX_PATH_USIZE],
+    // NOTE(aalhendi): laid out like game memory but only reserved, pages get committed as a snapshot
+    //  copies into them.
     pub memory_block: *mut (),
+    // NOTE(aalhendi): the pages held in memory_block, in order. Every other page of the snapshot is zero.
+    pub page_indices: *mut u32,
+    pub page_count: u32,
+    pub memory_hash: u64,
 }
 
 #[inline]
@@ -381,6 +746,16 @@ fn win32_load_game_code(source_dll_name: PCSTR, temp_dll_name: PCSTR) -> Win32Ga
         if !game_code_dll_handle.is_null() {
             game_code.game_code_dll = game_code_dll_handle;
 
+            let api_hash_proc = GetProcAddress(game_code_dll_handle, s!("game_get_api_hash"));
+            let get_api_hash = mem::transmute::<FARPROC, Option<GameGetApiHashFn>>(api_hash_proc);
+            if let Err(e) = game_api_check(get_api_hash) {
+                // NOTE(aalhendi): a stale DLL would read and write our structs with the wrong layout.
+                let source_dll_name = ffi::CStr::from_ptr(source_dll_name.cast());
+                eprintln!("Refusing to run {}: {e}", source_dll_name.to_string_lossy());
+                win32_unload_game_code(&mut game_code);
+                return game_code;
+            }
+
             let update_proc = GetProcAddress(game_code_dll_handle, s!("game_update_and_render"));
             let sound_proc = GetProcAddress(game_code_dll_handle, s!("game_get_sound_samples"));
 
@@ -401,6 +776,7 @@ fn win32_unload_game_code(game_code: &mut Win32GameCode) {
             // TODO(aalhendi): fallible
             FreeLibrary(game_code.game_code_dll);
         }
+        game_code.game_code_dll = HMODULE::default();
         game_code.is_valid = false;
         game_code.get_sound_samples = None;
         game_code.update_and_render = None;
@@ -878,11 +1254,14 @@ impl Win32OffscreenBuffer {
     }
 }
 
-pub fn run() {
+pub fn run(options: &PlatformOptions) -> Result<(), String> {
     unsafe {
         // TODO(aalhendi): fallible
         let module_handle = GetModuleHandleA(ptr::null());
-        let mut state = Win32State::default();
+        let mut state = Win32State {
+            bindings: options.load_bindings()?,
+            ..Default::default()
+        };
 
         // TODO(aalhendi): fallible
         QueryPerformanceFrequency(&mut PERF_COUNT_FREQUENCY);
@@ -890,9 +1269,23 @@ pub fn run() {
         state.win32_get_exe_file_name(module_handle);
 
         let mut source_dll_name = [0; MAX_PATH_USIZE];
-        let mut temp_dll_name = [0; MAX_PATH_USIZE];
-        state.win32_build_exe_path_file_name(c"hm.dll", &mut source_dll_name);
-        state.win32_build_exe_path_file_name(c"game_temp.dll", &mut temp_dll_name);
+        let mut temp_dll_names = [[0; MAX_PATH_USIZE]; 2];
+        match &options.game_lib {
+            Some(game_lib) => {
+                let game_lib = game_lib.to_string_lossy();
+                // NOTE(aalhendi): leave the last byte zero for the null terminator.
+                if game_lib.len() >= MAX_PATH_USIZE || game_lib.contains('\0') {
+                    return Err(format!("{game_lib:?} is not a valid library path"));
+                }
+                source_dll_name[..game_lib.len()].copy_from_slice(game_lib.as_bytes());
+            }
+            None => state.win32_build_exe_path_file_name(c"hm.dll", &mut source_dll_name),
+        }
+        // NOTE(aalhendi): new code is copied next to the old code, which stays loaded (and locked)
+        //  until we know the new code is usable.
+        state.win32_build_exe_path_file_name(c"game_temp_0.dll", &mut temp_dll_names[0]);
+        state.win32_build_exe_path_file_name(c"game_temp_1.dll", &mut temp_dll_names[1]);
+        let mut temp_dll_index = 0;
 
         // NOTE(aalhendi): Set windows scheduler granularity. This is used to make our sleep more accurate (granular).
         let desired_scheduler_ms = 1;
@@ -901,7 +1294,9 @@ pub fn run() {
             println!("Sleep is not granular. This is bad.");
         }
 
-        GLOBAL_BACKBUFFER.win32_resize_dib_section(960, 540);
+        let (backbuffer_width, backbuffer_height) = options.backbuffer_size;
+        GLOBAL_BACKBUFFER.win32_resize_dib_section(backbuffer_width, backbuffer_height);
+        GLOBAL_PAUSE = cfg!(feature = "internal_build") && options.start_paused;
 
         // TODO(aalhendi): getmodulehandlea check result fallible
         let instance = HINSTANCE::from(GetModuleHandleA(ptr::null()));
@@ -922,6 +1317,24 @@ pub fn run() {
         }
         debug_assert!(atom != 0);
 
+        // NOTE(aalhendi): the size asked for is the client area, the window is that plus its frame.
+        let (window_width, window_height) = match options.window_size {
+            Some((width, height)) => {
+                let mut window_rect = RECT {
+                    left: 0,
+                    top: 0,
+                    right: width,
+                    bottom: height,
+                };
+                AdjustWindowRect(&mut window_rect, WS_OVERLAPPEDWINDOW, FALSE);
+                (
+                    window_rect.right - window_rect.left,
+                    window_rect.bottom - window_rect.top,
+                )
+            }
+            None => (CW_USEDEFAULT, CW_USEDEFAULT),
+        };
+
         // TODO(aalhendi): fallible
         let window_handle = CreateWindowExA(
             0,
@@ -931,8 +1344,8 @@ pub fn run() {
             WS_OVERLAPPEDWINDOW | WS_VISIBLE,
             CW_USEDEFAULT,
             CW_USEDEFAULT,
-            CW_USEDEFAULT,
-            CW_USEDEFAULT,
+            window_width,
+            window_height,
             ptr::null_mut(),
             ptr::null_mut(),
             HINSTANCE::from(module_handle),
@@ -963,10 +1376,7 @@ pub fn run() {
         let (_ds, _primary_buffer, secondary_buffer) =
             match win32_init_dsound(window_handle, &mut sound_output) {
                 Ok(ds_and_primary_buffer) => ds_and_primary_buffer,
-                Err(e) => {
-                    eprintln!("Failed to initialize DirectSound: {e}");
-                    return;
-                }
+                Err(e) => return Err(format!("Failed to initialize DirectSound: {e}")),
             };
 
         win32_clear_sound_buffer(secondary_buffer, &mut sound_output);
@@ -994,10 +1404,11 @@ pub fn run() {
             PAGE_READWRITE,
         ) as *mut i16;
 
-        let permanent_storage_size = megabytes_to_bytes(64);
-        let transient_storage_size = gigabytes_to_bytes(1);
+        let permanent_storage_size = options.permanent_storage_size;
+        let transient_storage_size = options.transient_storage_size;
+        let frame_arena_size = options.frame_arena_size;
         // TODO(aalhendi): handle various memory footprints (USING SYSTEM METRICS)
-        let total_storage_size = permanent_storage_size + transient_storage_size;
+        let total_storage_size = permanent_storage_size + transient_storage_size + frame_arena_size;
         let base_address = {
             #[cfg(feature = "internal_build")]
             {
@@ -1012,75 +1423,58 @@ pub fn run() {
 
         state.total_size = total_storage_size;
         // TODO(aalhendi): look into using MEM_LARGE_PAGES?
+        // NOTE(aalhendi): MEM_WRITE_WATCH lets replay snapshots ask which pages the game wrote to.
         state.game_memory_block = VirtualAlloc(
             base_address as *mut ffi::c_void,
             state.total_size,
-            MEM_RESERVE | MEM_COMMIT,
+            MEM_RESERVE | MEM_COMMIT | MEM_WRITE_WATCH,
             PAGE_READWRITE,
         ) as *mut ();
         let permanent_storage = state.game_memory_block;
 
+        let (transient_storage, frame_arena) = split_transient_block(
+            permanent_storage.cast::<u8>().add(permanent_storage_size),
+            transient_storage_size,
+            frame_arena_size,
+        );
         let mut game_memory = GameMemory {
             is_initialized: false,
             permanent_storage_size,
             transient_storage_size,
             permanent_storage,
-            transient_storage: permanent_storage
-                .cast::<u8>()
-                .add(permanent_storage_size)
-                .cast::<()>(),
-            #[cfg(feature = "internal_build")]
-            debug_platform_read_entire_file,
-            #[cfg(feature = "internal_build")]
-            debug_platform_write_entire_file,
-            #[cfg(feature = "internal_build")]
-            debug_platform_free_file_memory,
+            transient_storage,
+            frame_arena,
+            platform: win32_platform_api(options.data_directory()?),
         };
 
-        for replay_idx in 0..state.replay_buffers.len() {
-            // TODO(aalhendi): Recording system still seems to take too long on record start
-            //  find out what Windows is doing and if we can speed up or defer some of that processing.
+        let max_page_count = state.total_size.div_ceil(REPLAY_PAGE_SIZE);
+        state.touched_pages = VirtualAlloc(
+            ptr::null(),
+            max_page_count * size_of::<*mut ffi::c_void>(),
+            MEM_RESERVE | MEM_COMMIT,
+            PAGE_READWRITE,
+        )
+        .cast::<*mut ffi::c_void>();
 
+        for replay_idx in 0..state.replay_buffers.len() {
             let mut fname = [0_u8; MAX_PATH_USIZE];
             state.win32_get_input_file_location(false, replay_idx as u32, &mut fname);
 
-            let file_handle = CreateFileA(
-                fname.as_ptr(),
-                GENERIC_WRITE | GENERIC_READ,
-                0,
-                ptr::null(),
-                CREATE_ALWAYS, // TODO(aalhendi): this might be cause of latency. see FSCTL_SET_SPARSE?
-                0,
-                0 as HANDLE,
-            );
-
-            if file_handle == INVALID_HANDLE_VALUE {
-                panic!("Win32: Failed to create replay file.");
-            }
-
-            let mapping_handle = CreateFileMappingA(
-                file_handle,
+            let memory_block =
+                VirtualAlloc(ptr::null(), state.total_size, MEM_RESERVE, PAGE_READWRITE);
+            let page_indices = VirtualAlloc(
                 ptr::null(),
+                max_page_count * size_of::<u32>(),
+                MEM_RESERVE | MEM_COMMIT,
                 PAGE_READWRITE,
-                (state.total_size >> 32) as u32,
-                (state.total_size & 0xFFFFFFFF) as u32,
-                ptr::null(),
             );
-
-            if mapping_handle.is_null() {
-                panic!("Win32: Failed to create file mapping.");
-            }
-
-            let view = MapViewOfFile(mapping_handle, FILE_MAP_ALL_ACCESS, 0, 0, state.total_size);
-
-            if view.Value.is_null() {
-                panic!("Win32: Failed to map view of file.");
+            if memory_block.is_null() || page_indices.is_null() {
+                panic!("Win32: Failed to allocate replay buffer.");
             }
 
             let replay_buffer = &mut state.replay_buffers[replay_idx];
-            replay_buffer.memory_block = view.Value as *mut ();
-            replay_buffer.file_handle = file_handle;
-            replay_buffer.memory_map = mapping_handle;
+            replay_buffer.memory_block = memory_block as *mut ();
+            replay_buffer.page_indices = page_indices.cast::<u32>();
             replay_buffer.file_name = fname;
         }
 
@@ -1091,6 +1485,31 @@ pub fn run() {
             panic!("Failed to allocate samples or permanent storage");
         }
 
+        if let Some(replay) = &options.replay {
+            #[cfg(feature = "internal_build")]
+            {
+                // NOTE(aalhendi): --replay borrows slot 0, its snapshot becomes whatever the replay
+                //  starts from.
+                if let Some(replay_state) = &options.replay_state {
+                    let memory = core::slice::from_raw_parts_mut(
+                        state.game_memory_block.cast::<u8>(),
+                        state.total_size,
+                    );
+                    replay_read_snapshot(replay_state, memory)?;
+                }
+                let file_name = CString::new(replay.to_string_lossy().as_bytes())
+                    .map_err(|_| format!("{} is not a valid replay path", replay.display()))?;
+                state.win32_take_snapshot(0);
+                state.win32_begin_input_playback_from(0, &file_name)?;
+            }
+            #[cfg(not(feature = "internal_build"))]
+            return Err(format!(
+                "Can't play back {}, replays need an internal build",
+                replay.display()
+            ));
+        }
+        let mut frames_left = options.frame_count;
+
         let mut input = [GameInput::default(), GameInput::default()];
         // NOTE(aalhendi): this is a hack to get around the fact that we can't have 2 mutable references to the same array
         let (new_input_slice, old_input_slice) = input.split_at_mut(1);
@@ -1112,19 +1531,37 @@ pub fn run() {
         let mut audio_latency_sec;
         let mut is_sound_valid = false;
 
-        let mut game = win32_load_game_code(source_dll_name.as_ptr(), temp_dll_name.as_ptr());
+        let mut game = win32_load_game_code(
+            source_dll_name.as_ptr(),
+            temp_dll_names[temp_dll_index].as_ptr(),
+        );
 
         // TODO(aalhendi): do we want to use rdtscp instead?
         let mut last_cycle_count = x86_64::_rdtsc();
 
         while GLOBAL_RUNNING {
+            if frames_left == Some(0) {
+                break;
+            }
             new_input.dt_for_frame = target_seconds_per_frame;
 
             let new_dll_write_time = win32_get_last_write_time(source_dll_name.as_ptr());
             if CompareFileTime(&new_dll_write_time, &game.last_write_time) != 0 {
-                win32_unload_game_code(&mut game);
-                game = win32_load_game_code(source_dll_name.as_ptr(), temp_dll_name.as_ptr());
-                game.last_write_time = new_dll_write_time;
+                let new_temp_dll_index = 1 - temp_dll_index;
+                let mut new_game = win32_load_game_code(
+                    source_dll_name.as_ptr(),
+                    temp_dll_names[new_temp_dll_index].as_ptr(),
+                );
+                new_game.last_write_time = new_dll_write_time;
+                if new_game.is_valid || !game.is_valid {
+                    win32_unload_game_code(&mut game);
+                    game = new_game;
+                    temp_dll_index = new_temp_dll_index;
+                } else {
+                    println!("Still running the previous game code");
+                    win32_unload_game_code(&mut new_game);
+                    game.last_write_time = new_dll_write_time;
+                }
             }
 
             let new_keyboard_controller = &mut new_input.controllers[0];
@@ -1136,7 +1573,12 @@ pub fn run() {
                 button.ended_down = old_keyboard_controller.buttons[i].ended_down;
             }
 
-            win32_process_pending_messages(&mut state, new_keyboard_controller);
+            new_input.mouse_z = 0;
+            win32_process_pending_messages(
+                &mut state,
+                new_keyboard_controller,
+                &mut new_input.mouse_z,
+            );
 
             if !GLOBAL_PAUSE {
                 let mut mouse_pos = POINT::default();
@@ -1146,7 +1588,6 @@ pub fn run() {
 
                 new_input.mouse_x = mouse_pos.x;
                 new_input.mouse_y = mouse_pos.y;
-                new_input.mouse_z = 0; // TODO(aalhendi): support scrollwheel?
 
                 // NOTE(aalhendi): If the high-order bit is 1, the key is down; otherwise, it is up.
                 // Since its an i16, the high order bit is the sign bit, so no need to check with & (1 << 15)
@@ -1228,95 +1669,51 @@ pub fn run() {
                         }
 
                         let threshold = 0.5_f32;
-                        // NOTE(aalhendi): fake dpad emulation from left stick
-                        win32_process_x_input_digital_button(
-                            XINPUT_GAMEPAD_BUTTON_FLAGS::from(
-                                (new_controller.left_stick_average_x < -threshold) as u16,
-                            ),
-                            old_controller.button_mut(GameButton::MoveLeft),
-                            XINPUT_GAMEPAD_DPAD_LEFT,
-                            new_controller.button_mut(GameButton::MoveLeft),
-                        );
-                        win32_process_x_input_digital_button(
-                            XINPUT_GAMEPAD_BUTTON_FLAGS::from(
-                                (new_controller.left_stick_average_x > threshold) as u16,
-                            ),
-                            old_controller.button_mut(GameButton::MoveRight),
-                            XINPUT_GAMEPAD_DPAD_RIGHT,
-                            new_controller.button_mut(GameButton::MoveRight),
-                        );
-                        win32_process_x_input_digital_button(
-                            XINPUT_GAMEPAD_BUTTON_FLAGS::from(
-                                (new_controller.left_stick_average_y < -threshold) as u16,
-                            ),
-                            old_controller.button_mut(GameButton::MoveUp),
-                            XINPUT_GAMEPAD_DPAD_UP,
-                            new_controller.button_mut(GameButton::MoveUp),
-                        );
-                        win32_process_x_input_digital_button(
-                            XINPUT_GAMEPAD_BUTTON_FLAGS::from(
-                                (new_controller.left_stick_average_y > threshold) as u16,
-                            ),
-                            old_controller.button_mut(GameButton::MoveDown),
-                            XINPUT_GAMEPAD_DPAD_DOWN,
-                            new_controller.button_mut(GameButton::MoveDown),
-                        );
-
-                        win32_process_x_input_digital_button(
-                            pad.wButtons,
-                            old_controller.button_mut(GameButton::ActionDown),
-                            XINPUT_GAMEPAD_A,
-                            new_controller.button_mut(GameButton::ActionDown),
-                        );
-
-                        win32_process_x_input_digital_button(
-                            pad.wButtons,
-                            old_controller.button_mut(GameButton::ActionRight),
-                            XINPUT_GAMEPAD_B,
-                            new_controller.button_mut(GameButton::ActionRight),
-                        );
-
-                        win32_process_x_input_digital_button(
-                            pad.wButtons,
-                            old_controller.button_mut(GameButton::ActionLeft),
-                            XINPUT_GAMEPAD_X,
-                            new_controller.button_mut(GameButton::ActionLeft),
-                        );
-
-                        win32_process_x_input_digital_button(
-                            pad.wButtons,
-                            old_controller.button_mut(GameButton::ActionUp),
-                            XINPUT_GAMEPAD_Y,
-                            new_controller.button_mut(GameButton::ActionUp),
-                        );
-
-                        win32_process_x_input_digital_button(
-                            pad.wButtons,
-                            old_controller.button_mut(GameButton::LeftShoulder),
-                            XINPUT_GAMEPAD_LEFT_SHOULDER,
-                            new_controller.button_mut(GameButton::LeftShoulder),
-                        );
-
-                        win32_process_x_input_digital_button(
-                            pad.wButtons,
-                            old_controller.button_mut(GameButton::RightShoulder),
-                            XINPUT_GAMEPAD_RIGHT_SHOULDER,
-                            new_controller.button_mut(GameButton::RightShoulder),
-                        );
-
-                        win32_process_x_input_digital_button(
-                            pad.wButtons,
-                            old_controller.button_mut(GameButton::Start),
-                            XINPUT_GAMEPAD_START,
-                            new_controller.button_mut(GameButton::Start),
-                        );
-
-                        win32_process_x_input_digital_button(
-                            pad.wButtons,
-                            old_controller.button_mut(GameButton::Back),
-                            XINPUT_GAMEPAD_BACK,
-                            new_controller.button_mut(GameButton::Back),
-                        );
+                        for button in GameButton::ALL {
+                            // NOTE(aalhendi): fake dpad emulation from left stick
+                            let is_stick_down = match button {
+                                GameButton::MoveLeft => {
+                                    new_controller.left_stick_average_x < -threshold
+                                }
+                                GameButton::MoveRight => {
+                                    new_controller.left_stick_average_x > threshold
+                                }
+                                GameButton::MoveUp => {
+                                    new_controller.left_stick_average_y > threshold
+                                }
+                                GameButton::MoveDown => {
+                                    new_controller.left_stick_average_y < -threshold
+                                }
+                                _ => false,
+                            };
+                            let is_down = is_stick_down
+                                || state.bindings.is_gamepad_button_down(
+                                    button,
+                                    |gamepad_button| {
+                                        let button_bit = match gamepad_button {
+                                            GamepadButton::A => XINPUT_GAMEPAD_A,
+                                            GamepadButton::B => XINPUT_GAMEPAD_B,
+                                            GamepadButton::X => XINPUT_GAMEPAD_X,
+                                            GamepadButton::Y => XINPUT_GAMEPAD_Y,
+                                            GamepadButton::LeftShoulder => {
+                                                XINPUT_GAMEPAD_LEFT_SHOULDER
+                                            }
+                                            GamepadButton::RightShoulder => {
+                                                XINPUT_GAMEPAD_RIGHT_SHOULDER
+                                            }
+                                            GamepadButton::Start => XINPUT_GAMEPAD_START,
+                                            GamepadButton::Back => XINPUT_GAMEPAD_BACK,
+                                        };
+                                        (pad.wButtons & button_bit) != 0
+                                    },
+                                );
+                            win32_process_x_input_digital_button(
+                                XINPUT_GAMEPAD_BUTTON_FLAGS::from(is_down as u16),
+                                old_controller.button_mut(button),
+                                1,
+                                new_controller.button_mut(button),
+                            );
+                        }
                     } else {
                         // NOTE(aalhendi): This controller is not available
                         new_controller.is_connected = false;
@@ -1339,16 +1736,20 @@ pub fn run() {
                     bytes_per_pixel: GLOBAL_BACKBUFFER.bytes_per_pixel,
                     memory: GLOBAL_BACKBUFFER.memory,
                 };
-                if state.input_recording_idx == 1 {
+                if state.input_recording_idx.is_some() {
                     state.win32_record_input(new_input);
                 }
-                if state.input_playing_idx == 1 {
+                if state.input_playing_idx.is_some() {
                     state.win32_playback_input(new_input);
                 }
 
+                game_memory.frame_arena.reset();
                 if let Some(update_and_render) = game.update_and_render {
                     update_and_render(&mut thread_ctx, &mut game_memory, new_input, &mut buffer);
                 }
+                if let Some(frames_left) = &mut frames_left {
+                    *frames_left -= 1;
+                }
 
                 /*
                 NOTE(aalhendi): Here is how sound output computation works.
@@ -1578,11 +1979,36 @@ pub fn run() {
             }
         }
     }
+    Ok(())
+}
+
+/// The key bindings call `virtual_key`. Like virtual keys themselves, letters go by what the layout
+/// types and the rest by where they are.
+fn win32_binding_key(virtual_key: VIRTUAL_KEY) -> Option<InputKey> {
+    let key = match virtual_key {
+        VK_A..=VK_Z | VK_0..=VK_9 => InputKey::Character(virtual_key as u8),
+        VK_F1..=VK_F12 => InputKey::Function((virtual_key - VK_F1 + 1) as u8),
+        VK_UP => InputKey::Up,
+        VK_DOWN => InputKey::Down,
+        VK_LEFT => InputKey::Left,
+        VK_RIGHT => InputKey::Right,
+        VK_SPACE => InputKey::Space,
+        VK_RETURN => InputKey::Enter,
+        VK_ESCAPE => InputKey::Escape,
+        VK_TAB => InputKey::Tab,
+        VK_BACK => InputKey::Backspace,
+        VK_SHIFT => InputKey::Shift,
+        VK_CONTROL => InputKey::Control,
+        VK_MENU => InputKey::Alt,
+        _ => return None,
+    };
+    Some(key)
 }
 
 unsafe fn win32_process_pending_messages(
     state: &mut Win32State,
     keyboard_controller: &mut GameControllerInput,
+    mouse_z: &mut i32,
 ) {
     let mut message = MSG::default();
     while unsafe { PeekMessageA(&mut message, ptr::null_mut(), 0, 0, PM_REMOVE) != FALSE } {
@@ -1590,89 +2016,67 @@ unsafe fn win32_process_pending_messages(
             WM_QUIT => unsafe {
                 GLOBAL_RUNNING = false;
             },
+            WM_MOUSEWHEEL => {
+                // NOTE(aalhendi): the signed wheel delta lives in the high word of wParam
+                let wheel_delta = ((message.wParam >> 16) & 0xFFFF) as u16 as i16 as i32;
+                state.mouse_wheel_remainder += wheel_delta;
+                *mouse_z += state.mouse_wheel_remainder / WHEEL_DELTA as i32;
+                state.mouse_wheel_remainder %= WHEEL_DELTA as i32;
+            }
             WM_KEYDOWN | WM_KEYUP | WM_SYSKEYDOWN | WM_SYSKEYUP => {
                 let virtual_key_code = message.wParam;
                 let was_down = (message.lParam & (1 << KEY_MESSAGE_WAS_DOWN_BIT)) != 0;
                 let is_down = (message.lParam & (1 << KEY_MESSAGE_IS_DOWN_BIT)) == 0;
                 let is_alt_down = (message.lParam & (1 << KEY_MESSAGE_IS_ALT_BIT)) != 0;
                 if was_down != is_down {
-                    match VIRTUAL_KEY::from(virtual_key_code as u16) {
-                        VK_W => win32_process_keyboard_message(
-                            keyboard_controller.button_mut(GameButton::MoveUp),
+                    let virtual_key = VIRTUAL_KEY::from(virtual_key_code as u16);
+                    let bound_button =
+                        win32_binding_key(virtual_key).and_then(|key| state.bindings.key(key));
+                    if let Some(button) = bound_button {
+                        win32_process_keyboard_message(
+                            keyboard_controller.button_mut(button),
                             is_down,
-                        ),
-                        VK_S => win32_process_keyboard_message(
-                            keyboard_controller.button_mut(GameButton::MoveDown),
-                            is_down,
-                        ),
-                        VK_A => win32_process_keyboard_message(
-                            keyboard_controller.button_mut(GameButton::MoveLeft),
-                            is_down,
-                        ),
-                        VK_D => win32_process_keyboard_message(
-                            keyboard_controller.button_mut(GameButton::MoveRight),
-                            is_down,
-                        ),
-                        VK_Q => win32_process_keyboard_message(
-                            keyboard_controller.button_mut(GameButton::LeftShoulder),
-                            is_down,
-                        ),
-                        VK_E => win32_process_keyboard_message(
-                            keyboard_controller.button_mut(GameButton::RightShoulder),
-                            is_down,
-                        ),
-                        VK_UP => win32_process_keyboard_message(
-                            keyboard_controller.button_mut(GameButton::ActionUp),
-                            is_down,
-                        ),
-                        VK_DOWN => win32_process_keyboard_message(
-                            keyboard_controller.button_mut(GameButton::ActionDown),
-                            is_down,
-                        ),
-                        VK_LEFT => win32_process_keyboard_message(
-                            keyboard_controller.button_mut(GameButton::ActionLeft),
-                            is_down,
-                        ),
-                        VK_RIGHT => win32_process_keyboard_message(
-                            keyboard_controller.button_mut(GameButton::ActionRight),
-                            is_down,
-                        ),
-                        VK_ESCAPE => win32_process_keyboard_message(
-                            keyboard_controller.button_mut(GameButton::Start),
-                            is_down,
-                        ),
-                        VK_SPACE => win32_process_keyboard_message(
-                            keyboard_controller.button_mut(GameButton::Back),
-                            is_down,
-                        ),
+                        );
+                    }
+                    match virtual_key {
                         VK_F4 if is_alt_down => {
                             println!("Alt + F4 pressed, quitting...");
                             unsafe {
                                 GLOBAL_RUNNING = false;
                             }
                         }
-                        VK_P => {
+                        // NOTE(aalhendi): a bound key belongs to the game, the debug keys only get
+                        //  unbound ones.
+                        _ if bound_button.is_some() => {}
+                        VK_P if is_down => {
                             #[cfg(feature = "internal_build")]
                             unsafe {
-                                if is_down {
-                                    GLOBAL_PAUSE = !GLOBAL_PAUSE
+                                GLOBAL_PAUSE = !GLOBAL_PAUSE;
+                            }
+                        }
+                        VK_L if is_down => {
+                            // NOTE(aalhendi): record, then loop what was recorded, then stop.
+                            #[cfg(feature = "internal_build")]
+                            match (state.input_recording_idx, state.input_playing_idx) {
+                                (Some(replay_idx), _) | (None, Some(replay_idx)) => {
+                                    state.win32_toggle_input_playback(replay_idx);
+                                }
+                                (None, None) => {
+                                    state.win32_toggle_input_recording(state.selected_replay_idx)
                                 }
                             }
                         }
-                        VK_L => {
+                        // NOTE(aalhendi): 1-4 loop that slot, Shift+1-4 record into it. Pressing the
+                        //  same again stops.
+                        VK_1 | VK_2 | VK_3 | VK_4 if is_down => {
                             #[cfg(feature = "internal_build")]
                             {
-                                if is_down {
-                                    if state.input_playing_idx == 0 {
-                                        if state.input_recording_idx == 0 {
-                                            state.win32_begin_recording_input(1);
-                                        } else {
-                                            state.win32_end_recording_input();
-                                            state.win32_begin_input_playback(1);
-                                        }
-                                    } else {
-                                        state.win32_end_input_playback();
-                                    }
+                                let replay_idx = (virtual_key_code as u16 - VK_1) as u32;
+                                state.selected_replay_idx = replay_idx;
+                                if unsafe { GetKeyState(VK_SHIFT as i32) } < 0 {
+                                    state.win32_toggle_input_recording(replay_idx);
+                                } else {
+                                    state.win32_toggle_input_playback(replay_idx);
                                 }
                             }
                         }
@@ -1738,10 +2142,241 @@ extern "system" fn win32_main_window_callback(
     }
 }
 
-#[cfg(feature = "internal_build")]
+/// The services the game reaches through `GameMemory::platform`.
+fn win32_platform_api(data_directory: PathBuf) -> PlatformApi {
+    PlatformApi {
+        version: PLATFORM_API_VERSION,
+        log: win32_platform_log,
+        get_wall_clock: win32_platform_get_wall_clock,
+        get_seconds_elapsed: win32_platform_get_seconds_elapsed,
+        read_cycle_counter: win32_platform_read_cycle_counter,
+        read_entire_file: win32_platform_read_entire_file,
+        write_entire_file: win32_platform_write_entire_file,
+        free_file_memory: win32_platform_free_file_memory,
+        file_system: win32_make_file_system(data_directory).cast::<PlatformFileSystem>(),
+        get_all_files_of_type_begin: win32_platform_get_all_files_of_type_begin,
+        get_all_files_of_type_end: win32_platform_get_all_files_of_type_end,
+        open_next_file: win32_platform_open_next_file,
+        open_file: win32_platform_open_file,
+        read_data_from_file: win32_platform_read_data_from_file,
+        file_error: win32_platform_file_error,
+        close_file: win32_platform_close_file,
+        allocate_memory: win32_platform_allocate_memory,
+        deallocate_memory: win32_platform_deallocate_memory,
+        work_queue: win32_make_work_queue().cast::<PlatformWorkQueue>(),
+        add_work_entry: win32_platform_add_work_entry,
+        complete_all_work: win32_platform_complete_all_work,
+    }
+}
+
+/// # Safety
+/// `message` must point to `message_size` readable bytes.
+unsafe extern "C" fn win32_platform_log(message: *const u8, message_size: usize) {
+    let message = unsafe { core::slice::from_raw_parts(message, message_size) };
+    println!("{}", String::from_utf8_lossy(message));
+}
+
+/// QueryPerformanceCounter ticks.
+extern "C" fn win32_platform_get_wall_clock() -> u64 {
+    win32_get_wall_clock() as u64
+}
+
+extern "C" fn win32_platform_get_seconds_elapsed(start: u64, end: u64) -> f32 {
+    win32_get_seconds_elapsed(0, end.wrapping_sub(start) as i64) as f32
+}
+
+extern "C" fn win32_platform_read_cycle_counter() -> u64 {
+    unsafe { x86_64::_rdtsc() }
+}
+
+extern "C" fn win32_platform_allocate_memory(size: usize) -> *mut ffi::c_void {
+    unsafe { VirtualAlloc(ptr::null(), size, MEM_RESERVE | MEM_COMMIT, PAGE_READWRITE) }
+}
+
+/// # Safety
+/// `memory` must be null or come from win32_platform_allocate_memory, and not be used again.
+unsafe extern "C" fn win32_platform_deallocate_memory(memory: *mut ffi::c_void, _size: usize) {
+    if !memory.is_null() && unsafe { VirtualFree(memory, 0, MEM_RELEASE) } == FALSE {
+        eprintln!("Failed to free memory");
+    }
+}
+
+const WORK_QUEUE_ENTRY_COUNT: u32 = 256;
+// NOTE(aalhendi): the main thread works too while it waits in complete_all_work.
+const MAX_WORKER_THREAD_COUNT: usize = 15;
+
+#[derive(Clone, Copy)]
+struct Win32WorkQueueEntry {
+    // NOTE(aalhendi): Option so that zeroed memory is a valid entry.
+    callback: Option<PlatformWorkQueueCallback>,
+    data: *mut ffi::c_void,
+}
+
+/// A ring of entries with one writer, the main thread, and any number of readers. Zeroed memory is an
+/// empty queue, apart from the semaphore.
+struct Win32WorkQueue {
+    completion_goal: AtomicU32,
+    completion_count: AtomicU32,
+    next_entry_to_write: AtomicU32,
+    next_entry_to_read: AtomicU32,
+    next_thread_index: AtomicU32,
+    // NOTE(aalhendi): counts the entries no worker has woken up for yet.
+    semaphore: HANDLE,
+    entries: [UnsafeCell<Win32WorkQueueEntry>; WORK_QUEUE_ENTRY_COUNT as usize],
+}
+
+fn win32_make_work_queue() -> *mut Win32WorkQueue {
+    let queue = unsafe {
+        VirtualAlloc(
+            ptr::null(),
+            size_of::<Win32WorkQueue>(),
+            MEM_RESERVE | MEM_COMMIT,
+            PAGE_READWRITE,
+        )
+    }
+    .cast::<Win32WorkQueue>();
+    assert!(!queue.is_null(), "Failed to allocate the work queue");
+
+    let worker_thread_count = std::thread::available_parallelism()
+        .map_or(1, |count| count.get())
+        .saturating_sub(1)
+        .min(MAX_WORKER_THREAD_COUNT);
+    unsafe {
+        let semaphore = CreateSemaphoreExA(
+            ptr::null(),
+            0,
+            // NOTE(aalhendi): posts past the maximum fail, which is fine, a worker is awake anyway.
+            MAX_WORKER_THREAD_COUNT as i32,
+            ptr::null(),
+            0,
+            SEMAPHORE_ALL_ACCESS,
+        );
+        assert!(
+            !semaphore.is_null(),
+            "Failed to create the work queue semaphore. Error Code: {}",
+            GetLastError()
+        );
+        (*queue).semaphore = semaphore;
+        (*queue).next_thread_index.store(1, Ordering::Relaxed);
+    }
+
+    for _ in 0..worker_thread_count {
+        let thread = unsafe {
+            CreateThread(
+                ptr::null(),
+                0,
+                Some(win32_work_queue_thread_proc),
+                queue.cast::<ffi::c_void>(),
+                0,
+                ptr::null_mut(),
+            )
+        };
+        if thread.is_null() {
+            // NOTE(aalhendi): not fatal, complete_all_work gets through the queue on its own.
+            eprintln!("Failed to start a worker thread. Error Code: {}", unsafe {
+                GetLastError()
+            });
+            break;
+        }
+        unsafe {
+            let _ = CloseHandle(thread);
+        }
+    }
+
+    queue
+}
+
+unsafe extern "system" fn win32_work_queue_thread_proc(queue: *mut ffi::c_void) -> u32 {
+    let queue = unsafe { &*queue.cast::<Win32WorkQueue>() };
+    let mut thread = ThreadContext {
+        thread_index: queue.next_thread_index.fetch_add(1, Ordering::Relaxed),
+    };
+    loop {
+        if win32_do_next_work_queue_entry(queue, &mut thread) {
+            unsafe {
+                WaitForSingleObjectEx(queue.semaphore, INFINITE, FALSE);
+            }
+        }
+    }
+}
+
+/// Runs the next entry if there is one. Returns true when the queue was empty, i.e. the caller can sleep.
+fn win32_do_next_work_queue_entry(queue: &Win32WorkQueue, thread: &mut ThreadContext) -> bool {
+    let original_next_entry_to_read = queue.next_entry_to_read.load(Ordering::Acquire);
+    if original_next_entry_to_read == queue.next_entry_to_write.load(Ordering::Acquire) {
+        return true;
+    }
+
+    let new_next_entry_to_read = (original_next_entry_to_read + 1) % WORK_QUEUE_ENTRY_COUNT;
+    if queue
+        .next_entry_to_read
+        .compare_exchange(
+            original_next_entry_to_read,
+            new_next_entry_to_read,
+            Ordering::AcqRel,
+            Ordering::Relaxed,
+        )
+        .is_ok()
+    {
+        // NOTE(aalhendi): winning the exchange makes the entry ours. The writer can't reuse it until
+        //  next_entry_to_read comes all the way around, which a full queue assert stops first.
+        let entry = unsafe { *queue.entries[original_next_entry_to_read as usize].get() };
+        if let Some(callback) = entry.callback {
+            unsafe { callback(thread, entry.data) };
+        }
+        queue.completion_count.fetch_add(1, Ordering::Release);
+    }
+    false
+}
+
+/// # Safety
+/// `queue` must be the work_queue from win32_platform_api, and this must be called on the main thread.
+unsafe extern "C" fn win32_platform_add_work_entry(
+    queue: *mut PlatformWorkQueue,
+    callback: PlatformWorkQueueCallback,
+    data: *mut ffi::c_void,
+) {
+    let queue = unsafe { &*queue.cast::<Win32WorkQueue>() };
+    let next_entry_to_write = queue.next_entry_to_write.load(Ordering::Relaxed);
+    let new_next_entry_to_write = (next_entry_to_write + 1) % WORK_QUEUE_ENTRY_COUNT;
+    assert!(
+        new_next_entry_to_write != queue.next_entry_to_read.load(Ordering::Acquire),
+        "Work queue is full"
+    );
+    unsafe {
+        *queue.entries[next_entry_to_write as usize].get() = Win32WorkQueueEntry {
+            callback: Some(callback),
+            data,
+        };
+    }
+    queue.completion_goal.fetch_add(1, Ordering::Relaxed);
+    queue
+        .next_entry_to_write
+        .store(new_next_entry_to_write, Ordering::Release);
+    unsafe {
+        ReleaseSemaphore(queue.semaphore, 1, ptr::null_mut());
+    }
+}
+
+/// # Safety
+/// `queue` must be the work_queue from win32_platform_api, and this must be called on the main thread.
+unsafe extern "C" fn win32_platform_complete_all_work(
+    thread: &mut ThreadContext,
+    queue: *mut PlatformWorkQueue,
+) {
+    let queue = unsafe { &*queue.cast::<Win32WorkQueue>() };
+    while queue.completion_count.load(Ordering::Acquire)
+        != queue.completion_goal.load(Ordering::Relaxed)
+    {
+        win32_do_next_work_queue_entry(queue, thread);
+    }
+    queue.completion_goal.store(0, Ordering::Relaxed);
+    queue.completion_count.store(0, Ordering::Relaxed);
+}
+
 /// # Safety
-/// TODO(aalhendi): idk. write this up
-pub unsafe extern "C" fn debug_platform_free_file_memory(
+/// `ptr` must be null or a pointer handed out by win32_platform_read_entire_file that hasn't been freed yet.
+unsafe extern "C" fn win32_platform_free_file_memory(
     _thread: &mut ThreadContext,
     ptr: *mut ffi::c_void,
 ) {
@@ -1755,8 +2390,7 @@ pub unsafe extern "C" fn debug_platform_free_file_memory(
     }
 }
 
-#[cfg(feature = "internal_build")]
-pub extern "C" fn debug_platform_write_entire_file(
+extern "C" fn win32_platform_write_entire_file(
     _thread: &mut ThreadContext,
     filename: *const ffi::c_char,
     memory_size: u32,
@@ -1807,11 +2441,10 @@ pub extern "C" fn debug_platform_write_entire_file(
     }
 }
 
-#[cfg(feature = "internal_build")]
-pub extern "C" fn debug_platform_read_entire_file(
+extern "C" fn win32_platform_read_entire_file(
     thread: &mut ThreadContext,
     filename: *const ffi::c_char,
-) -> DebugPlatformReadFileResult {
+) -> PlatformReadFileResult {
     unsafe {
         use interface::safe_truncate_i64_to_u32;
         use windows_sys::Win32::{
@@ -1840,7 +2473,7 @@ pub extern "C" fn debug_platform_read_entire_file(
         if file_size == 0 {
             // NOTE(aalhendi): Reading an empty file isn't an error. We can return null or allocate a 1 byte buffer if caller expects a non-null ptr.
             // we will return null and let the caller handle it.
-            return DebugPlatformReadFileResult {
+            return PlatformReadFileResult {
                 memory: ptr::null_mut(),
                 size: 0,
             };
@@ -1866,7 +2499,7 @@ pub extern "C" fn debug_platform_read_entire_file(
         );
         // TODO(aalhendi): check result
         if read_result == FALSE || bytes_read != file_size_u32 {
-            debug_platform_free_file_memory(thread, memory_ptr);
+            win32_platform_free_file_memory(thread, memory_ptr);
             eprintln!("Failed to read file: {read_result:?}");
             // sound because we just freed the memory
             memory_ptr = ptr::null_mut();
@@ -1877,13 +2510,298 @@ pub extern "C" fn debug_platform_read_entire_file(
         // so for now we just close it manually.
         // TODO(aalhendi): handle the result
         let _ = CloseHandle(file_handle);
-        DebugPlatformReadFileResult {
+        PlatformReadFileResult {
             memory: memory_ptr,
             size: file_size_u32,
         }
     }
 }
 
+// NOTE(aalhendi): INVALID_HANDLE_VALUE, which can't be cast to an integer in a const.
+const WIN32_CLOSED_FILE: usize = usize::MAX;
+
+struct Win32FileSystem {
+    data_directory: PathBuf,
+}
+
+struct Win32FileGroup {
+    find_handle: HANDLE,
+    // NOTE(aalhendi): the file open_next_file gets next, if has_next_file.
+    find_data: WIN32_FIND_DATAA,
+    has_next_file: bool,
+    file_system: *const Win32FileSystem,
+}
+
+// NOTE(aalhendi): like the work queue, it lives as long as the PlatformApi that points at it, which
+//  is the rest of the process.
+fn win32_make_file_system(data_directory: PathBuf) -> *mut Win32FileSystem {
+    let file_system = unsafe {
+        VirtualAlloc(
+            ptr::null(),
+            size_of::<Win32FileSystem>(),
+            MEM_RESERVE | MEM_COMMIT,
+            PAGE_READWRITE,
+        )
+    }
+    .cast::<Win32FileSystem>();
+    assert!(!file_system.is_null(), "Failed to allocate the file system");
+    unsafe {
+        file_system.write(Win32FileSystem { data_directory });
+    }
+    file_system
+}
+
+fn win32_file_error(handle: &mut PlatformFileHandle, message: &str) {
+    eprintln!("File error: {message}");
+    handle.has_errors = true;
+}
+
+fn win32_data_path(file_system: &Win32FileSystem, file_name: &str) -> Option<CString> {
+    let path = file_system.data_directory.join(file_name);
+    CString::new(path.to_string_lossy().as_bytes()).ok()
+}
+
+fn win32_open_file_handle(file_system: &Win32FileSystem, file_name: &str) -> PlatformFileHandle {
+    let mut handle = PlatformFileHandle {
+        has_errors: false,
+        platform: WIN32_CLOSED_FILE,
+    };
+    let Some(path) = win32_data_path(file_system, file_name) else {
+        win32_file_error(
+            &mut handle,
+            &format!("{file_name:?} is not a valid file name"),
+        );
+        return handle;
+    };
+
+    let file_handle = unsafe {
+        CreateFileA(
+            path.as_ptr().cast::<u8>(),
+            GENERIC_READ,
+            FILE_SHARE_READ,
+            ptr::null(),
+            OPEN_EXISTING,
+            FILE_ATTRIBUTE_NORMAL,
+            ptr::null_mut(),
+        )
+    };
+    if file_handle == INVALID_HANDLE_VALUE {
+        win32_file_error(
+            &mut handle,
+            &format!("Failed to open {path:?}. Error Code: {}", unsafe {
+                GetLastError()
+            }),
+        );
+    } else {
+        handle.platform = file_handle as usize;
+    }
+    handle
+}
+
+fn win32_find_first_file(wildcard: &CString, group: &mut Win32FileGroup) {
+    group.find_handle =
+        unsafe { FindFirstFileA(wildcard.as_ptr().cast::<u8>(), &mut group.find_data) };
+    group.has_next_file = group.find_handle != INVALID_HANDLE_VALUE;
+}
+
+fn win32_find_close(group: &mut Win32FileGroup) {
+    if group.find_handle != INVALID_HANDLE_VALUE {
+        unsafe {
+            FindClose(group.find_handle);
+        }
+        group.find_handle = INVALID_HANDLE_VALUE;
+    }
+    group.has_next_file = false;
+}
+
+/// The name of the group's next file, skipping directories.
+fn win32_next_file_name(group: &mut Win32FileGroup) -> Option<String> {
+    while group.has_next_file {
+        let is_directory = group.find_data.dwFileAttributes & FILE_ATTRIBUTE_DIRECTORY != 0;
+        let file_name = unsafe { ffi::CStr::from_ptr(group.find_data.cFileName.as_ptr()) }
+            .to_string_lossy()
+            .into_owned();
+        group.has_next_file =
+            unsafe { FindNextFileA(group.find_handle, &mut group.find_data) } != FALSE;
+        if !is_directory {
+            return Some(file_name);
+        }
+    }
+    None
+}
+
+/// # Safety
+/// `file_system` must come from win32_platform_api.
+unsafe extern "C" fn win32_platform_get_all_files_of_type_begin(
+    file_system: *mut PlatformFileSystem,
+    file_type: PlatformFileType,
+) -> PlatformFileGroup {
+    let empty = PlatformFileGroup {
+        file_count: 0,
+        platform: ptr::null_mut(),
+    };
+    let file_system = file_system.cast::<Win32FileSystem>();
+    let wildcard = win32_data_path(
+        unsafe { &*file_system },
+        &format!("*.{}", file_type.extension()),
+    );
+    let Some(wildcard) = wildcard else {
+        return empty;
+    };
+    let group = unsafe {
+        VirtualAlloc(
+            ptr::null(),
+            size_of::<Win32FileGroup>(),
+            MEM_RESERVE | MEM_COMMIT,
+            PAGE_READWRITE,
+        )
+    }
+    .cast::<Win32FileGroup>();
+    if group.is_null() {
+        return empty;
+    }
+    let group_ref = unsafe { &mut *group };
+    group_ref.file_system = file_system;
+
+    // NOTE(aalhendi): one pass to count, then start over for open_next_file.
+    let mut file_count = 0;
+    win32_find_first_file(&wildcard, group_ref);
+    while win32_next_file_name(group_ref).is_some() {
+        file_count += 1;
+    }
+    win32_find_close(group_ref);
+    win32_find_first_file(&wildcard, group_ref);
+
+    PlatformFileGroup {
+        file_count,
+        platform: group.cast::<ffi::c_void>(),
+    }
+}
+
+/// # Safety
+/// `group` must come from win32_platform_get_all_files_of_type_begin.
+unsafe extern "C" fn win32_platform_get_all_files_of_type_end(group: &mut PlatformFileGroup) {
+    let win32_group = group.platform.cast::<Win32FileGroup>();
+    if !win32_group.is_null() {
+        win32_find_close(unsafe { &mut *win32_group });
+        unsafe {
+            let _ = VirtualFree(win32_group.cast(), 0, MEM_RELEASE);
+        }
+    }
+    group.file_count = 0;
+    group.platform = ptr::null_mut();
+}
+
+/// # Safety
+/// `group` must come from win32_platform_get_all_files_of_type_begin.
+unsafe extern "C" fn win32_platform_open_next_file(
+    group: &mut PlatformFileGroup,
+) -> PlatformFileHandle {
+    let win32_group = group.platform.cast::<Win32FileGroup>();
+    let file_name = if win32_group.is_null() {
+        None
+    } else {
+        win32_next_file_name(unsafe { &mut *win32_group })
+    };
+
+    match file_name {
+        Some(file_name) => {
+            win32_open_file_handle(unsafe { &*(*win32_group).file_system }, &file_name)
+        }
+        None => {
+            let mut handle = PlatformFileHandle {
+                has_errors: false,
+                platform: WIN32_CLOSED_FILE,
+            };
+            win32_file_error(&mut handle, "There are no more files in the group");
+            handle
+        }
+    }
+}
+
+/// # Safety
+/// `file_system` must come from win32_platform_api and `filename` must be a valid C string.
+unsafe extern "C" fn win32_platform_open_file(
+    file_system: *mut PlatformFileSystem,
+    filename: *const ffi::c_char,
+) -> PlatformFileHandle {
+    let file_system = unsafe { &*file_system.cast::<Win32FileSystem>() };
+    let filename = unsafe { ffi::CStr::from_ptr(filename) };
+    win32_open_file_handle(file_system, &filename.to_string_lossy())
+}
+
+/// # Safety
+/// `handle` must come from one of the open functions and `dest` must point to `size` writable bytes.
+unsafe extern "C" fn win32_platform_read_data_from_file(
+    handle: &mut PlatformFileHandle,
+    offset: u64,
+    size: u64,
+    dest: *mut ffi::c_void,
+) {
+    let dest = unsafe { core::slice::from_raw_parts_mut(dest.cast::<u8>(), size as usize) };
+    let mut bytes_done = 0;
+    // NOTE(aalhendi): ReadFile takes a u32 size, so big reads go in pieces. The offset goes in the
+    //  OVERLAPPED, so reads of the same file from different threads don't fight over the file position.
+    while !handle.has_errors && bytes_done < dest.len() {
+        let position = offset + bytes_done as u64;
+        let mut overlapped = OVERLAPPED {
+            Anonymous: OVERLAPPED_0 {
+                Anonymous: OVERLAPPED_0_0 {
+                    Offset: position as u32,
+                    OffsetHigh: (position >> 32) as u32,
+                },
+            },
+            ..Default::default()
+        };
+        let bytes_to_read = (dest.len() - bytes_done).min(u32::MAX as usize) as u32;
+        let mut bytes_read = 0_u32;
+        let read_result = unsafe {
+            ReadFile(
+                handle.platform as HANDLE,
+                dest[bytes_done..].as_mut_ptr(),
+                bytes_to_read,
+                &mut bytes_read,
+                &mut overlapped,
+            )
+        };
+        if read_result == FALSE || bytes_read == 0 {
+            win32_file_error(
+                handle,
+                &format!(
+                    "Failed to read {size} bytes at offset {offset}. Error Code: {}",
+                    unsafe { GetLastError() }
+                ),
+            );
+        }
+        bytes_done += bytes_read as usize;
+    }
+    if handle.has_errors {
+        dest.fill(0);
+    }
+}
+
+/// # Safety
+/// `message` must point to `message_size` readable bytes.
+unsafe extern "C" fn win32_platform_file_error(
+    handle: &mut PlatformFileHandle,
+    message: *const u8,
+    message_size: usize,
+) {
+    let message = unsafe { core::slice::from_raw_parts(message, message_size) };
+    win32_file_error(handle, &String::from_utf8_lossy(message));
+}
+
+/// # Safety
+/// `handle` must come from one of the open functions.
+unsafe extern "C" fn win32_platform_close_file(handle: &mut PlatformFileHandle) {
+    if handle.platform != WIN32_CLOSED_FILE {
+        unsafe {
+            let _ = CloseHandle(handle.platform as HANDLE);
+        }
+        handle.platform = WIN32_CLOSED_FILE;
+    }
+}
+
 // NOTE(aalhendi): `windows-sys` in Rust does NOT support COM interfaces like DirectSound.
 // The alternative `windows` crate does support COM interfaces, but it's fat.
 #[allow(non_snake_case)]